@@ -0,0 +1,313 @@
+use crate::fourcc::FourCC;
+use crate::parser::{FormatExtension, SubFormat, WaveFormatEx, BI_BITFIELDS, BI_RGB};
+use crate::state::StreamContext;
+
+/// codec of a stream, from the video fourccs or the audio WAVE format tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodecId {
+    /// uncompressed RGB or YUV frames
+    RawVideo,
+    Mjpeg,
+    /// lossless JPEG
+    Ljpeg,
+    JpegLs,
+    Png,
+    HuffYuv,
+    Ffv1,
+    Lagarith,
+    Dv,
+    Mpeg1Video,
+    Mpeg2Video,
+    /// MPEG-4 part 2, as muxed by Xvid or DivX 4 and later
+    Mpeg4,
+    MsMpeg4v1,
+    MsMpeg4v2,
+    /// also DivX 3
+    MsMpeg4v3,
+    H263,
+    H264,
+    Hevc,
+    Wmv1,
+    Wmv2,
+    Wmv3,
+    Vc1,
+    Vp8,
+    Vp9,
+    Cinepak,
+    MsVideo1,
+    Indeo3,
+    Indeo4,
+    Indeo5,
+    Pcm,
+    IeeeFloat,
+    Alaw,
+    Mulaw,
+    MsAdpcm,
+    ImaAdpcm,
+    /// MPEG-1 audio layer I or II
+    Mp2,
+    Mp3,
+    Aac,
+    Ac3,
+    Dts,
+    Wmav1,
+    Wmav2,
+    WmaPro,
+    Flac,
+    /// a video or subtitle fourcc we don't list
+    Unknown(FourCC),
+    /// an audio format tag we don't list
+    UnknownFormatTag(u16),
+}
+
+/// fourccs of each video codec, in uppercase
+const VIDEO_CODECS: &[(CodecId, &[&[u8; 4]])] = &[
+    (
+        CodecId::RawVideo,
+        &[
+            b"RAW ", b"UYVY", b"YUY2", b"YUYV", b"YVYU", b"YV12", b"I420", b"IYUV", b"NV12",
+            b"Y800", b"GREY",
+        ],
+    ),
+    (
+        CodecId::Mjpeg,
+        &[b"MJPG", b"AVRN", b"JPGL", b"DMB1", b"AVDJ", b"JPEG"],
+    ),
+    (CodecId::Ljpeg, &[b"LJPG"]),
+    (CodecId::JpegLs, &[b"MJLS"]),
+    (CodecId::Png, &[b"MPNG", b"PNG1"]),
+    (CodecId::HuffYuv, &[b"HFYU", b"FFVH"]),
+    (CodecId::Ffv1, &[b"FFV1"]),
+    (CodecId::Lagarith, &[b"LAGS"]),
+    (
+        CodecId::Dv,
+        &[
+            b"DVSD", b"DV25", b"DV50", b"DVHD", b"DVSL", b"CDVC", b"CDVH", b"DVCP", b"DVPP",
+        ],
+    ),
+    (CodecId::Mpeg1Video, &[b"MPG1", b"PIM1", b"\x01\0\0\x10"]),
+    (
+        CodecId::Mpeg2Video,
+        &[b"MPG2", b"MPEG", b"MMES", b"\x02\0\0\x10"],
+    ),
+    (
+        CodecId::Mpeg4,
+        &[
+            b"XVID", b"DIVX", b"DX50", b"FMP4", b"MP4V", b"3IV2", b"M4S2", b"RMP4", b"DIV5",
+            b"DIV6", b"MP4S",
+        ],
+    ),
+    (CodecId::MsMpeg4v1, &[b"MPG4", b"DIV1"]),
+    (CodecId::MsMpeg4v2, &[b"MP42", b"DIV2"]),
+    (
+        CodecId::MsMpeg4v3,
+        &[b"MP43", b"DIV3", b"DIV4", b"MPG3", b"AP41", b"COL1"],
+    ),
+    (CodecId::H263, &[b"H263", b"U263", b"M263"]),
+    (
+        CodecId::H264,
+        &[b"H264", b"X264", b"AVC1", b"DAVC", b"VSSH"],
+    ),
+    (CodecId::Hevc, &[b"HEVC", b"H265", b"X265", b"HVC1"]),
+    (CodecId::Wmv1, &[b"WMV1"]),
+    (CodecId::Wmv2, &[b"WMV2"]),
+    (CodecId::Wmv3, &[b"WMV3"]),
+    (CodecId::Vc1, &[b"WVC1", b"WMVA"]),
+    (CodecId::Vp8, &[b"VP80"]),
+    (CodecId::Vp9, &[b"VP90"]),
+    (CodecId::Cinepak, &[b"CVID"]),
+    (CodecId::MsVideo1, &[b"CRAM", b"MSVC", b"WHAM"]),
+    (CodecId::Indeo3, &[b"IV31", b"IV32"]),
+    (CodecId::Indeo4, &[b"IV41"]),
+    (CodecId::Indeo5, &[b"IV50"]),
+];
+
+/// WAVE format tags of each audio codec
+const AUDIO_CODECS: &[(CodecId, &[u16])] = &[
+    (CodecId::Pcm, &[0x0001]),
+    (CodecId::MsAdpcm, &[0x0002]),
+    (CodecId::IeeeFloat, &[0x0003]),
+    (CodecId::Alaw, &[0x0006]),
+    (CodecId::Mulaw, &[0x0007]),
+    (CodecId::ImaAdpcm, &[0x0011]),
+    (CodecId::Mp2, &[0x0050]),
+    (CodecId::Mp3, &[0x0055]),
+    (CodecId::Aac, &[0x00ff, 0x1600, 0x1610, 0x706d, 0xa106]),
+    (CodecId::Ac3, &[0x2000, 0x0092]),
+    (CodecId::Dts, &[0x2001, 0x0008]),
+    (CodecId::Wmav1, &[0x0160]),
+    (CodecId::Wmav2, &[0x0161]),
+    (CodecId::WmaPro, &[0x0162]),
+    (CodecId::Flac, &[0xf1ac]),
+];
+
+impl CodecId {
+    /// video codecs are found from the bitmap compression, then the stream handler, as
+    /// some muxers only fill one of them
+    pub fn new(stream: &StreamContext) -> CodecId {
+        match stream {
+            StreamContext::Video(v) => {
+                let compression = CodecId::from_fourcc(v.bitmap.compression());
+                let handler = CodecId::from_fourcc(v.stream.fcc_handler());
+                match compression {
+                    CodecId::Unknown(_) if !matches!(handler, CodecId::Unknown(_)) => handler,
+                    codec => codec,
+                }
+            }
+            StreamContext::Audio(a) => CodecId::from_wave_format(&a.format),
+            StreamContext::Subtitle(s) => CodecId::Unknown(s.stream.fcc_handler()),
+        }
+    }
+
+    /// codec of a video fourcc, regardless of its case
+    pub fn from_fourcc(fourcc: FourCC) -> CodecId {
+        if fourcc == BI_RGB || fourcc == BI_BITFIELDS {
+            return CodecId::RawVideo;
+        }
+
+        let mut upper = fourcc.0;
+        upper.make_ascii_uppercase();
+        VIDEO_CODECS
+            .iter()
+            .find(|(_, fourccs)| fourccs.contains(&&upper))
+            .map_or(CodecId::Unknown(fourcc), |(codec, _)| *codec)
+    }
+
+    /// codec of a WAVE format tag
+    pub fn from_format_tag(format_tag: u16) -> CodecId {
+        AUDIO_CODECS
+            .iter()
+            .find(|(_, tags)| tags.contains(&format_tag))
+            .map_or(CodecId::UnknownFormatTag(format_tag), |(codec, _)| *codec)
+    }
+
+    /// codec of an audio format, `WAVE_FORMAT_EXTENSIBLE` ones being found from their
+    /// sub format
+    pub fn from_wave_format(format: &WaveFormatEx) -> CodecId {
+        let extensible = match &format.extension {
+            Some(FormatExtension::Extensible(e)) => e,
+            _ => return CodecId::from_format_tag(format.format_tag),
+        };
+
+        match extensible.sub_format {
+            SubFormat::Pcm => CodecId::Pcm,
+            SubFormat::IeeeFloat => CodecId::IeeeFloat,
+            SubFormat::Alaw => CodecId::Alaw,
+            SubFormat::Mulaw => CodecId::Mulaw,
+            SubFormat::Adpcm => CodecId::MsAdpcm,
+            SubFormat::Mpeg => CodecId::Mp2,
+            SubFormat::MpegLayer3 => CodecId::Mp3,
+            SubFormat::Ac3 => CodecId::Ac3,
+            SubFormat::Dts => CodecId::Dts,
+            SubFormat::Aac => CodecId::Aac,
+            SubFormat::WaveFormat(tag) => CodecId::from_format_tag(tag),
+            SubFormat::Unknown(_) => CodecId::UnknownFormatTag(format.format_tag),
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        match self {
+            CodecId::Unknown(_) => false,
+            CodecId::UnknownFormatTag(_) => false,
+            codec => VIDEO_CODECS.iter().any(|(c, _)| c == codec),
+        }
+    }
+
+    pub fn is_audio(&self) -> bool {
+        match self {
+            CodecId::UnknownFormatTag(_) => true,
+            codec => AUDIO_CODECS.iter().any(|(c, _)| c == codec),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use super::*;
+    use crate::state::{advance, State};
+
+    const drop: &[u8] = include_bytes!("../assets/drop.avi");
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    fn streams(data: &[u8]) -> Vec<StreamContext> {
+        let mut state = State::Initial;
+        let mut offset = 0;
+        loop {
+            let (advancing, next) = advance(state, &data[offset..]).unwrap();
+            offset += advancing;
+            match next {
+                State::Blocks(ctx) if ctx.in_movi() => return ctx.streams().to_vec(),
+                next => state = next,
+            }
+        }
+    }
+
+    #[test]
+    fn fourccs() {
+        for (fourcc, codec) in [
+            (b"XVID", CodecId::Mpeg4),
+            (b"divx", CodecId::Mpeg4),
+            (b"DX50", CodecId::Mpeg4),
+            (b"MJPG", CodecId::Mjpeg),
+            (b"H264", CodecId::H264),
+            (b"avc1", CodecId::H264),
+            (b"HFYU", CodecId::HuffYuv),
+            (b"FFV1", CodecId::Ffv1),
+            (b"dvsd", CodecId::Dv),
+        ] {
+            assert_eq!(CodecId::from_fourcc(FourCC::new(fourcc)), codec);
+        }
+
+        assert_eq!(CodecId::from_fourcc(BI_RGB), CodecId::RawVideo);
+        let unknown = FourCC::new(b"abcd");
+        assert_eq!(CodecId::from_fourcc(unknown), CodecId::Unknown(unknown));
+        assert!(CodecId::H264.is_video() && !CodecId::H264.is_audio());
+    }
+
+    #[test]
+    fn format_tags() {
+        assert_eq!(CodecId::from_format_tag(0x0055), CodecId::Mp3);
+        assert_eq!(CodecId::from_format_tag(0x2000), CodecId::Ac3);
+        assert_eq!(
+            CodecId::from_format_tag(0x1234),
+            CodecId::UnknownFormatTag(0x1234)
+        );
+        assert!(CodecId::Mp3.is_audio() && !CodecId::Mp3.is_video());
+    }
+
+    #[test]
+    fn stream_codecs() {
+        let codecs: Vec<_> = streams(verona).iter().map(CodecId::new).collect();
+        assert_eq!(codecs, vec![CodecId::MsMpeg4v2, CodecId::MsAdpcm]);
+
+        let codecs: Vec<_> = streams(drop).iter().map(StreamContext::codec).collect();
+        assert_eq!(codecs, vec![CodecId::Indeo4]);
+    }
+
+    #[test]
+    fn strf_extra_data() {
+        // drop.avi as HuffYUV, with codec data after the bitmap header taken from the JUNK
+        let extra_data = [0x18, 0x04, 0, 0, 0, 0, 0, 0];
+        let junk_size = 1816 - extra_data.len() as u32;
+        let mut data = [&drop[..212], &extra_data, b"JUNK", &junk_size.to_le_bytes()].concat();
+        data.resize(2036, 0);
+        data.extend_from_slice(&drop[2036..]);
+        data[16..20].copy_from_slice(&200u32.to_le_bytes());
+        data[92..96].copy_from_slice(&124u32.to_le_bytes());
+        data[168..172].copy_from_slice(&48u32.to_le_bytes());
+        data[112..116].copy_from_slice(b"HFYU");
+        data[188..192].copy_from_slice(b"HFYU");
+
+        let streams = streams(&data);
+        assert_eq!(streams[0].codec(), CodecId::HuffYuv);
+        match &streams[0] {
+            StreamContext::Video(v) => {
+                assert_eq!(v.bitmap.size(), 40);
+                assert_eq!(v.bitmap.extra_data(), &extra_data);
+                assert_eq!(v.bitmap.to_bytes(), &data[172..220]);
+            }
+            _ => panic!("expected a video stream"),
+        }
+    }
+}
@@ -0,0 +1,644 @@
+use std::cmp::max;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use crate::error::{AviError, AviErrorKind};
+use crate::fourcc::FourCC;
+use crate::index::{idx1, idx1_base, opendml_index, Idx1Base, OpenDmlIndex};
+use crate::info::{info, Metadata};
+use crate::parser::{block_header, header, BlockHeader};
+
+/// the shift applied to the data when the `INFO` list does not fit is a multiple of this,
+/// keeping the sector alignment of the chunks
+const SHIFT_ALIGNMENT: u64 = 2048;
+
+/// how much data is moved at once when shifting the file content
+const COPY_SIZE: usize = 1 << 20;
+
+/// how the file was updated by `InfoEditor::save()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveMode {
+    /// the `INFO` list fit in the space of the old one and the `JUNK` around it
+    InPlace,
+    /// the data after the headers was moved by `shift` bytes, and the indexes fixed
+    Rewritten { shift: u64 },
+}
+
+/// edits the `LIST INFO` metadata of an AVI file without remuxing it
+///
+/// the new list goes between `hdrl` and `movi`, in the space of the `INFO` lists and
+/// `JUNK` chunks already there. When it does not fit, everything from the `movi` list
+/// on is moved further in the file, and the absolute offsets of the `idx1` and OpenDML
+/// indexes are updated.
+pub struct InfoEditor<F> {
+    file: F,
+    metadata: Metadata,
+    layout: Layout,
+}
+
+/// the chunks of the first `RIFF` chunk the editor needs to know about
+struct Layout {
+    file_len: u64,
+    riff_size: u32,
+    /// the `LIST INFO` and `JUNK` chunks right before the `movi` list
+    space: Range<u64>,
+    /// `LIST INFO` chunks outside of `space`, turned into `JUNK` when saving
+    stale_info: Vec<u64>,
+    /// offsets of the `indx` chunks in `hdrl`
+    super_indexes: Vec<u64>,
+    /// offset of the `idx1` chunk, when its entries hold absolute offsets
+    absolute_idx1: Option<u64>,
+}
+
+impl<F: Read + Write + Seek> InfoEditor<F> {
+    /// reads the headers of the first `RIFF` chunk and the metadata of all its `INFO`
+    /// lists
+    pub fn new(mut file: F) -> Result<InfoEditor<F>, AviError> {
+        let mut metadata = Metadata::default();
+        let layout = scan(&mut file, &mut metadata)?;
+
+        Ok(InfoEditor {
+            file,
+            metadata,
+            layout,
+        })
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    /// writes the metadata in a single `LIST INFO` chunk, followed by `JUNK` filling the
+    /// rest of the space
+    pub fn save(&mut self) -> Result<SaveMode, AviError> {
+        let list = if self.metadata.is_empty() {
+            Vec::new()
+        } else {
+            let content = self.metadata.to_bytes();
+            let mut list = BlockHeader::new(FourCC::LIST, content.len() as u32).to_bytes();
+            list.extend_from_slice(&content);
+            list
+        };
+
+        let needed = list.len() as u64;
+        let available = self.layout.space.end - self.layout.space.start;
+        // the remaining space needs room for a `JUNK` header, unless there is none left
+        let mode = if needed == available || needed + 8 <= available {
+            SaveMode::InPlace
+        } else {
+            let shift = (needed + 8 - available).div_ceil(SHIFT_ALIGNMENT) * SHIFT_ALIGNMENT;
+            self.shift(shift)?;
+            SaveMode::Rewritten { shift }
+        };
+
+        let space = self.layout.space.clone();
+        let mut data = list;
+        if space.end - space.start > needed {
+            let junk_size = (space.end - space.start - needed - 8) as u32;
+            data.extend_from_slice(&BlockHeader::new(FourCC::JUNK, junk_size).to_bytes());
+            data.resize((space.end - space.start) as usize, 0);
+        }
+        self.write_at(space.start, &data)?;
+
+        for offset in self.layout.stale_info.clone() {
+            self.write_at(offset, FourCC::JUNK.as_bytes())?;
+        }
+        self.file
+            .flush()
+            .map_err(|e| AviError::io(space.start as usize, e))?;
+
+        self.layout = scan(&mut self.file, &mut Metadata::default())?;
+        Ok(mode)
+    }
+
+    /// moves everything after `space` by `shift` bytes, then fixes the sizes and offsets
+    /// pointing there
+    fn shift(&mut self, shift: u64) -> Result<(), AviError> {
+        let start = self.layout.space.end;
+        let riff_size = u64::from(self.layout.riff_size) + shift;
+        if riff_size > u64::from(u32::MAX) {
+            return Err(self.too_large(start));
+        }
+
+        // from the end, so the data is not overwritten before being moved
+        let mut buffer = vec![0; COPY_SIZE];
+        let mut end = self.layout.file_len;
+        while end > start {
+            let block_start = max(start, end.saturating_sub(COPY_SIZE as u64));
+            let block = &mut buffer[..(end - block_start) as usize];
+            self.read_at(block_start, block)?;
+            self.write_at(block_start + shift, block)?;
+            end = block_start;
+        }
+        self.layout.file_len += shift;
+        self.write_at(4, &(riff_size as u32).to_le_bytes())?;
+
+        for offset in self.layout.super_indexes.clone() {
+            self.shift_super_index(offset, start, shift)?;
+        }
+        if let Some(offset) = self.layout.absolute_idx1 {
+            self.shift_idx1(offset + shift, start, shift)?;
+        }
+
+        self.layout.riff_size = riff_size as u32;
+        self.layout.space.end += shift;
+        Ok(())
+    }
+
+    /// updates the entries of a super index, then the standard indexes it points to
+    fn shift_super_index(&mut self, offset: u64, start: u64, shift: u64) -> Result<(), AviError> {
+        let data = self.read_chunk(offset)?;
+        let entries = match opendml_index(&data) {
+            Ok((_, OpenDmlIndex::Super(s))) => s.entries,
+            _ => return Ok(()),
+        };
+        let longs_per_entry = u16::from_le_bytes([data[8], data[9]]) as u64;
+
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.offset < start {
+                continue;
+            }
+            let index_offset = entry.offset + shift;
+            let position = offset + 32 + i as u64 * 4 * longs_per_entry;
+            self.write_at(position, &index_offset.to_le_bytes())?;
+
+            self.shift_standard_index(index_offset, start, shift)?;
+        }
+
+        Ok(())
+    }
+
+    /// moves the base offset of a standard index, or its entries when the base is before
+    /// the moved data, like the start of the first `RIFF` chunk
+    fn shift_standard_index(
+        &mut self,
+        offset: u64,
+        start: u64,
+        shift: u64,
+    ) -> Result<(), AviError> {
+        let data = self.read_chunk(offset)?;
+        let index = match opendml_index(&data) {
+            Ok((_, OpenDmlIndex::Standard(s))) => s,
+            _ => return Ok(()),
+        };
+
+        if index.base_offset >= start {
+            let base_offset = index.base_offset + shift;
+            return self.write_at(offset + 20, &base_offset.to_le_bytes());
+        }
+
+        let longs_per_entry = u16::from_le_bytes([data[8], data[9]]) as usize;
+        let mut content = data[32..].to_vec();
+        for (entry, bytes) in index
+            .entries
+            .iter()
+            .zip(content.chunks_mut(4 * longs_per_entry))
+        {
+            // the size is between the offset and the second field offset
+            for (position, field) in [(0, Some(entry.offset)), (8, entry.second_field_offset)] {
+                let field = match field {
+                    Some(f) if index.base_offset + u64::from(f) >= start => f,
+                    _ => continue,
+                };
+                let shifted =
+                    u32::try_from(u64::from(field) + shift).map_err(|_| self.too_large(offset))?;
+                bytes[position..position + 4].copy_from_slice(&shifted.to_le_bytes());
+            }
+        }
+
+        self.write_at(offset + 32, &content)
+    }
+
+    fn shift_idx1(&mut self, offset: u64, start: u64, shift: u64) -> Result<(), AviError> {
+        let data = self.read_chunk(offset)?;
+        let (_, entries) = idx1(&data).map_err(|_| self.malformed(offset))?;
+
+        let mut content = data[8..8 + entries.len() * 16].to_vec();
+        for (entry, bytes) in entries.iter().zip(content.chunks_mut(16)) {
+            let entry_offset = u64::from(entry.offset);
+            if entry_offset >= start {
+                let shifted =
+                    u32::try_from(entry_offset + shift).map_err(|_| self.too_large(offset))?;
+                bytes[8..12].copy_from_slice(&shifted.to_le_bytes());
+            }
+        }
+
+        self.write_at(offset + 8, &content)
+    }
+
+    fn read_chunk(&mut self, offset: u64) -> Result<Vec<u8>, AviError> {
+        read_chunk(&mut self.file, offset, self.layout.file_len)
+    }
+
+    fn read_at(&mut self, offset: u64, data: &mut [u8]) -> Result<(), AviError> {
+        read_at(&mut self.file, offset, data)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), AviError> {
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.file.write_all(data))
+            .map_err(|e| AviError::io(offset as usize, e))
+    }
+
+    fn malformed(&self, offset: u64) -> AviError {
+        AviError::new(
+            offset as usize,
+            None,
+            AviErrorKind::Malformed(nom::error::ErrorKind::Verify),
+        )
+    }
+
+    fn too_large(&self, offset: u64) -> AviError {
+        AviError::new(offset as usize, None, AviErrorKind::FileTooLarge)
+    }
+}
+
+/// walks the top level chunks of the first `RIFF` chunk, adding the `INFO` lists found to
+/// `metadata`
+fn scan(file: &mut (impl Read + Seek), metadata: &mut Metadata) -> Result<Layout, AviError> {
+    let file_len = file
+        .seek(SeekFrom::End(0))
+        .map_err(|e| AviError::io(0, e))?;
+
+    let mut data = [0; 12];
+    read_at(file, 0, &mut data)?;
+    let (_, riff) = header(&data).map_err(|e| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => AviError::from_nom(&data, 0, e),
+        nom::Err::Incomplete(_) => AviError::new(0, None, AviErrorKind::UnexpectedChunk),
+    })?;
+    let riff_end = (8 + u64::from(riff.file_size)).min(file_len);
+
+    let mut stale_info = Vec::new();
+    // the lists before `movi`, the ones not in the final space are stale too
+    let mut header_info = Vec::new();
+    let mut super_indexes = Vec::new();
+    let mut absolute_idx1 = None;
+    let mut space_start = None;
+    let mut movi = None;
+
+    let mut offset = 12;
+    while offset + 12 <= riff_end {
+        read_at(file, offset, &mut data)?;
+        let (_, chunk) = block_header(&data).map_err(|_| missing_movi(offset))?;
+        let list_type = FourCC::from_slice(&data[8..]).expect("4 bytes");
+
+        match (chunk.tag(), list_type) {
+            (FourCC::LIST, FourCC::HDRL) => {
+                let hdrl = read_chunk(file, offset, file_len)?;
+                hdrl_chunks(
+                    hdrl.get(12..).unwrap_or_default(),
+                    offset + 12,
+                    &mut |tag, position, chunk| match tag {
+                        FourCC::INDX => super_indexes.push(position),
+                        FourCC::INFO => {
+                            stale_info.push(position);
+                            if let Ok((_, m)) = info(chunk) {
+                                metadata.extend(m);
+                            }
+                        }
+                        _ => {}
+                    },
+                );
+                space_start = None;
+            }
+            (FourCC::LIST, FourCC::INFO) => {
+                let list = read_chunk(file, offset, file_len)?;
+                if let Ok((_, m)) = info(list.get(12..).unwrap_or_default()) {
+                    metadata.extend(m);
+                }
+                match movi {
+                    Some(_) => stale_info.push(offset),
+                    None => {
+                        header_info.push(offset);
+                        space_start.get_or_insert(offset);
+                    }
+                }
+            }
+            (FourCC::LIST, FourCC::MOVI) if movi.is_none() => movi = Some(offset),
+            (FourCC::IDX1, _) if movi.is_some() && absolute_idx1.is_none() => {
+                // a broken index is ignored, like the reader does
+                let index = read_chunk(file, offset, file_len).unwrap_or_default();
+                if let Ok((_, entries)) = idx1(&index) {
+                    let movi_offset = movi.expect("found before") + 8;
+                    if idx1_base(&entries, movi_offset) == Idx1Base::File {
+                        absolute_idx1 = Some(offset);
+                    }
+                }
+            }
+            (tag, _) if movi.is_none() && is_junk(tag) => {
+                space_start.get_or_insert(offset);
+            }
+            _ if movi.is_none() => space_start = None,
+            _ => {}
+        }
+
+        offset += 8 + u64::from(chunk.size()) + u64::from(chunk.size() & 1);
+    }
+
+    let movi = movi.ok_or_else(|| missing_movi(offset))?;
+    let space = space_start.unwrap_or(movi)..movi;
+    stale_info.extend(header_info.into_iter().filter(|o| !space.contains(o)));
+
+    Ok(Layout {
+        file_len,
+        riff_size: riff.file_size,
+        space,
+        stale_info,
+        super_indexes,
+        absolute_idx1,
+    })
+}
+
+/// calls `f` with the tag, absolute offset and content of the `indx` chunks and `INFO`
+/// lists of the `hdrl` content
+fn hdrl_chunks(data: &[u8], offset: u64, f: &mut impl FnMut(FourCC, u64, &[u8])) {
+    let mut input = data;
+    while let Ok((content, chunk)) = block_header(input) {
+        let size = (chunk.size() as usize).min(content.len());
+        let position = offset + (data.len() - input.len()) as u64;
+        let content = &content[..size];
+
+        match (chunk.tag(), FourCC::from_slice(content)) {
+            (FourCC::LIST, Some(FourCC::STRL)) => hdrl_chunks(&content[4..], position + 12, f),
+            (FourCC::LIST, Some(FourCC::INFO)) => f(FourCC::INFO, position, &content[4..]),
+            (FourCC::INDX, _) => f(FourCC::INDX, position, content),
+            _ => {}
+        }
+
+        let next = 8 + size + (size & 1);
+        input = &input[next.min(input.len())..];
+    }
+}
+
+fn is_junk(tag: FourCC) -> bool {
+    matches!(tag.as_bytes(), b"JUNK" | b"JUNQ" | b"PAD ")
+}
+
+fn missing_movi(offset: u64) -> AviError {
+    AviError::new(
+        offset as usize,
+        None,
+        AviErrorKind::MissingChunk(FourCC::MOVI),
+    )
+}
+
+/// reads a whole chunk, header included, checking its size against the file length
+/// before allocating for it
+fn read_chunk(
+    file: &mut (impl Read + Seek),
+    offset: u64,
+    file_len: u64,
+) -> Result<Vec<u8>, AviError> {
+    let mut header = [0; 8];
+    read_at(file, offset, &mut header)?;
+    let (_, chunk) = block_header(&header).expect("8 bytes");
+    if offset + 8 + u64::from(chunk.size()) > file_len {
+        return Err(AviError::new(
+            offset as usize,
+            Some(chunk.tag()),
+            AviErrorKind::Malformed(nom::error::ErrorKind::Eof),
+        ));
+    }
+
+    let mut data = vec![0; 8 + chunk.size() as usize];
+    read_at(file, offset, &mut data)?;
+    Ok(data)
+}
+
+fn read_at(file: &mut (impl Read + Seek), offset: u64, data: &mut [u8]) -> Result<(), AviError> {
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(data))
+        .map_err(|e| AviError::io(offset as usize, e))
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::info::InfoTag;
+    use crate::reader::{AviReader, Packet};
+    use crate::writer::AviWriter;
+
+    const drop: &[u8] = include_bytes!("../assets/drop.avi");
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    fn read_all(data: &[u8]) -> (Metadata, Vec<Packet>) {
+        let mut reader = AviReader::new(Cursor::new(data)).unwrap();
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            packets.push(packet);
+        }
+        (reader.metadata().clone(), packets)
+    }
+
+    /// same packets, `shift` bytes later in the file
+    fn assert_moved(packets: &[Packet], original: &[Packet], shift: u64) {
+        assert_eq!(packets.len(), original.len());
+        for (p, o) in packets.iter().zip(original) {
+            assert_eq!(
+                (p.stream, &p.data, p.keyframe),
+                (o.stream, &o.data, o.keyframe)
+            );
+            assert_eq!(p.offset, o.offset + shift);
+        }
+    }
+
+    #[test]
+    fn edit_in_place() {
+        let mut editor = InfoEditor::new(Cursor::new(verona.to_vec())).unwrap();
+        assert!(editor.metadata().is_empty());
+        editor.metadata_mut().insert(InfoTag::Title, "Verona");
+        editor.metadata_mut().insert(InfoTag::Copyright, "CC BY");
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+        let data = editor.into_inner().into_inner();
+
+        assert_eq!(data.len(), verona.len());
+        assert_eq!(&data[390..402], b"LIST\x22\0\0\0INFO");
+        assert_eq!(&data[2036..], &verona[2036..]);
+        let (metadata, packets) = read_all(&data);
+        assert_eq!(metadata.get(InfoTag::Title), Some("Verona"));
+        assert_eq!(metadata.get(InfoTag::Copyright), Some("CC BY"));
+        assert_moved(&packets, &read_all(verona).1, 0);
+
+        // removing the entries gives the space back to the `JUNK` chunk
+        let mut editor = InfoEditor::new(Cursor::new(data)).unwrap();
+        assert_eq!(editor.metadata(), &metadata);
+        editor.metadata_mut().remove(InfoTag::Title);
+        editor.metadata_mut().remove(InfoTag::Copyright);
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+        let data = editor.into_inner().into_inner();
+        assert_eq!(&data[390..398], b"JUNK\x66\x06\0\0");
+        assert!(read_all(&data).0.is_empty());
+    }
+
+    #[test]
+    fn edit_with_rewrite() {
+        let mut editor = InfoEditor::new(Cursor::new(drop.to_vec())).unwrap();
+        editor
+            .metadata_mut()
+            .insert(InfoTag::Comment, "a".repeat(3000));
+        assert_eq!(editor.save().unwrap(), SaveMode::Rewritten { shift: 2048 });
+
+        // the next edits fit in the space left
+        editor.metadata_mut().insert(InfoTag::Title, "Drop");
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+        let data = editor.into_inner().into_inner();
+
+        assert_eq!(data.len(), drop.len() + 2048);
+        let riff_size = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        assert_eq!(
+            riff_size,
+            u32::from_le_bytes(drop[4..8].try_into().unwrap()) + 2048
+        );
+        let (metadata, packets) = read_all(&data);
+        assert_eq!(metadata.get(InfoTag::Title), Some("Drop"));
+        assert_eq!(metadata.get(InfoTag::Comment).unwrap().len(), 3000);
+        assert_moved(&packets, &read_all(drop).1, 2048);
+    }
+
+    #[test]
+    fn rewrite_absolute_idx1() {
+        // the same file, with `idx1` offsets relative to the start of the file
+        let mut data = drop.to_vec();
+        for entry in data[672724..672724 + 2912].chunks_mut(16) {
+            let offset = u32::from_le_bytes(entry[8..12].try_into().unwrap()) + 2044;
+            entry[8..12].copy_from_slice(&offset.to_le_bytes());
+        }
+        let original = read_all(&data).1;
+
+        let mut editor = InfoEditor::new(Cursor::new(data)).unwrap();
+        editor
+            .metadata_mut()
+            .insert(InfoTag::Comment, "a".repeat(3000));
+        assert_eq!(editor.save().unwrap(), SaveMode::Rewritten { shift: 2048 });
+        let data = editor.into_inner().into_inner();
+
+        let reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        let index = &reader.index().unwrap().stream(0).unwrap().entries;
+        assert_eq!(index.len(), 182);
+        assert_eq!(index[0].offset, 2048 + 2048);
+        assert_moved(&read_all(&data).1, &original, 2048);
+    }
+
+    #[test]
+    fn rewrite_opendml_indexes() {
+        let mut reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let mut writer = AviWriter::new_opendml(Cursor::new(Vec::new()), reader.streams()).unwrap();
+        writer.set_segment_size(200_000);
+        while let Some(p) = reader.next_packet().unwrap() {
+            writer.write_packet(p.stream, &p.data, p.keyframe).unwrap();
+        }
+        let remuxed = writer.finish().unwrap().into_inner();
+
+        // no `JUNK` after the headers of our own files
+        let mut editor = InfoEditor::new(Cursor::new(remuxed.clone())).unwrap();
+        editor.metadata_mut().insert(InfoTag::Software, "avi-rs");
+        assert_eq!(editor.save().unwrap(), SaveMode::Rewritten { shift: 2048 });
+        let data = editor.into_inner().into_inner();
+
+        let mut original = AviReader::new(Cursor::new(&remuxed[..])).unwrap();
+        let mut edited = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert_eq!(edited.metadata().get(InfoTag::Software), Some("avi-rs"));
+        for stream in 0..2 {
+            let original = &original.index().unwrap().stream(stream).unwrap().entries;
+            let edited = &edited.index().unwrap().stream(stream).unwrap().entries;
+            assert_eq!(original.len(), edited.len());
+            assert!(original
+                .iter()
+                .zip(edited)
+                .all(|(o, e)| e.offset == o.offset + 2048 && e.size == o.size));
+        }
+
+        let mut packets = Vec::new();
+        while let Some(p) = edited.next_packet().unwrap() {
+            packets.push(p);
+        }
+        let mut expected = Vec::new();
+        while let Some(p) = original.next_packet().unwrap() {
+            expected.push(p);
+        }
+        assert_moved(&packets, &expected, 2048);
+    }
+
+    #[test]
+    fn oversized_chunks() {
+        let mut data = drop.to_vec();
+        data[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        let e = InfoEditor::new(Cursor::new(data)).err().unwrap();
+        assert_eq!((e.offset, e.tag), (12, Some(FourCC::LIST)));
+        assert_eq!(e.kind, AviErrorKind::Malformed(nom::error::ErrorKind::Eof));
+
+        // a broken `idx1` does not prevent editing
+        let mut data = drop.to_vec();
+        data[672720..672724].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut editor = InfoEditor::new(Cursor::new(data)).unwrap();
+        editor.metadata_mut().insert(InfoTag::Title, "Drop");
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+    }
+
+    #[test]
+    fn info_list_before_other_chunks() {
+        // `hdrl`, `LIST INFO`, another chunk, then the `JUNK` the new list goes into
+        let list = b"LIST\x12\0\0\0INFOINAM\x06\0\0\0Title\0";
+        let other = [&b"IDIT\x12\0\0\0"[..], &[0; 18]].concat();
+        let junk_size = 2036 - 390 - list.len() - other.len() - 8;
+        let mut data = [
+            &verona[..390],
+            list,
+            &other,
+            b"JUNK",
+            &(junk_size as u32).to_le_bytes(),
+        ]
+        .concat();
+        data.resize(2036, 0);
+        data.extend_from_slice(&verona[2036..]);
+        assert_eq!(read_all(&data).0.get(InfoTag::Title), Some("Title"));
+
+        let mut editor = InfoEditor::new(Cursor::new(data)).unwrap();
+        editor.metadata_mut().remove(InfoTag::Title);
+        editor.metadata_mut().insert(InfoTag::Artist, "Shakespeare");
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+        let data = editor.into_inner().into_inner();
+
+        assert_eq!(&data[390..394], b"JUNK");
+        assert_eq!(&data[416..420], b"IDIT");
+        let (metadata, packets) = read_all(&data);
+        assert_eq!(metadata.get(InfoTag::Title), None);
+        assert_eq!(metadata.get(InfoTag::Artist), Some("Shakespeare"));
+        assert_moved(&packets, &read_all(verona).1, 0);
+    }
+
+    #[test]
+    fn stale_info_lists() {
+        // an `INFO` list in `hdrl`, which is replaced by the one written before `movi`
+        let list = b"LIST\x12\0\0\0INFOINAM\x06\0\0\0Title\0";
+        let junk_size = 1638 - list.len() as u32;
+        let mut data = [&verona[..390], list, b"JUNK", &junk_size.to_le_bytes()].concat();
+        data.resize(2036, 0);
+        data.extend_from_slice(&verona[2036..]);
+        data[16..20].copy_from_slice(&(370 + list.len() as u32).to_le_bytes());
+
+        let mut editor = InfoEditor::new(Cursor::new(data)).unwrap();
+        assert_eq!(editor.metadata().get(InfoTag::Title), Some("Title"));
+        editor.metadata_mut().insert(InfoTag::Title, "Verona");
+        assert_eq!(editor.save().unwrap(), SaveMode::InPlace);
+        let data = editor.into_inner().into_inner();
+
+        assert_eq!(&data[390..394], b"JUNK");
+        let (metadata, packets) = read_all(&data);
+        assert_eq!(metadata.get(InfoTag::Title), Some("Verona"));
+        assert_eq!(metadata.len(), 1);
+        assert_moved(&packets, &read_all(verona).1, 0);
+    }
+}
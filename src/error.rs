@@ -0,0 +1,110 @@
+use std::fmt;
+
+use nom::{error::ErrorKind, Offset};
+
+use crate::fourcc::FourCC;
+
+/// why the file could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum AviErrorKind {
+    /// the chunk content does not match its expected layout
+    Malformed(ErrorKind),
+    /// a chunk or list extends past the end of its parent
+    ChunkOverflow {
+        end_offset: usize,
+        parent_end: usize,
+    },
+    /// the chunk is valid but not allowed at this position
+    UnexpectedChunk,
+    /// the stream type cannot be demuxed yet
+    UnsupportedStream,
+    /// the state machine was advanced from a state it cannot leave
+    InvalidState,
+    /// a mandatory chunk is not in the file
+    MissingChunk(FourCC),
+    /// no stream has this number
+    UnknownStream(u16),
+    /// chunk ids only have two digits for the stream number
+    TooManyStreams(usize),
+    /// the data does not fit in the chunk size fields
+    FileTooLarge,
+    /// reading the underlying data failed
+    Io(std::io::ErrorKind),
+}
+
+/// error returned by the parsers and the state machine
+///
+/// `offset` is the absolute position in the file where parsing failed, and `tag` the
+/// id of the chunk being parsed, when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct AviError {
+    pub offset: usize,
+    pub tag: Option<FourCC>,
+    pub kind: AviErrorKind,
+}
+
+impl AviError {
+    pub fn new(offset: usize, tag: Option<FourCC>, kind: AviErrorKind) -> AviError {
+        AviError { offset, tag, kind }
+    }
+
+    /// error for the chunk starting `input`, located at `offset` in the file
+    pub fn chunk(input: &[u8], offset: usize, kind: AviErrorKind) -> AviError {
+        AviError::new(offset, chunk_tag(input), kind)
+    }
+
+    /// I/O error while reading the file at `offset`
+    pub fn io(offset: usize, e: std::io::Error) -> AviError {
+        AviError::new(offset, None, AviErrorKind::Io(e.kind()))
+    }
+
+    /// converts an error from a parser applied on `input`, located at `offset` in the file
+    pub fn from_nom(input: &[u8], offset: usize, e: nom::error::Error<&[u8]>) -> AviError {
+        AviError::new(
+            offset + input.offset(e.input),
+            chunk_tag(input),
+            AviErrorKind::Malformed(e.code),
+        )
+    }
+}
+
+fn chunk_tag(input: &[u8]) -> Option<FourCC> {
+    FourCC::from_slice(input)
+}
+
+impl fmt::Display for AviErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AviErrorKind::Malformed(e) => write!(f, "malformed chunk ({:?})", e),
+            AviErrorKind::ChunkOverflow {
+                end_offset,
+                parent_end,
+            } => write!(
+                f,
+                "chunk ends at {} after its parent's end at {}",
+                end_offset, parent_end
+            ),
+            AviErrorKind::UnexpectedChunk => write!(f, "unexpected chunk"),
+            AviErrorKind::UnsupportedStream => write!(f, "unsupported stream type"),
+            AviErrorKind::InvalidState => write!(f, "invalid parser state"),
+            AviErrorKind::MissingChunk(tag) => write!(f, "missing '{}' chunk", tag),
+            AviErrorKind::UnknownStream(n) => write!(f, "unknown stream {}", n),
+            AviErrorKind::TooManyStreams(n) => write!(f, "{} streams, at most 100 allowed", n),
+            AviErrorKind::FileTooLarge => write!(f, "file too large"),
+            AviErrorKind::Io(e) => write!(f, "I/O error ({:?})", e),
+        }
+    }
+}
+
+impl fmt::Display for AviError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        if let Some(tag) = self.tag {
+            write!(f, " in chunk '{}'", tag)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for AviError {}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// four character code identifying chunks, lists, stream types and codecs
+///
+/// it is stored as read from the file, so codec fourccs held in a little endian `u32`
+/// convert with `From<u32>`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FourCC(pub [u8; 4]);
+
+impl FourCC {
+    pub const RIFF: FourCC = FourCC(*b"RIFF");
+    pub const LIST: FourCC = FourCC(*b"LIST");
+    pub const AVI: FourCC = FourCC(*b"AVI ");
+    pub const AVIX: FourCC = FourCC(*b"AVIX");
+    pub const HDRL: FourCC = FourCC(*b"hdrl");
+    pub const AVIH: FourCC = FourCC(*b"avih");
+    pub const STRL: FourCC = FourCC(*b"strl");
+    pub const STRH: FourCC = FourCC(*b"strh");
+    pub const STRF: FourCC = FourCC(*b"strf");
+    pub const STRD: FourCC = FourCC(*b"strd");
+    pub const STRN: FourCC = FourCC(*b"strn");
+    pub const VPRP: FourCC = FourCC(*b"vprp");
+    pub const INDX: FourCC = FourCC(*b"indx");
+    pub const ODML: FourCC = FourCC(*b"odml");
+    pub const DMLH: FourCC = FourCC(*b"dmlh");
+    pub const INFO: FourCC = FourCC(*b"INFO");
+    pub const MOVI: FourCC = FourCC(*b"movi");
+    pub const REC: FourCC = FourCC(*b"rec ");
+    pub const IDX1: FourCC = FourCC(*b"idx1");
+    pub const JUNK: FourCC = FourCC(*b"JUNK");
+    pub const VIDS: FourCC = FourCC(*b"vids");
+    pub const AUDS: FourCC = FourCC(*b"auds");
+    pub const TXTS: FourCC = FourCC(*b"txts");
+
+    pub const fn new(code: &[u8; 4]) -> FourCC {
+        FourCC(*code)
+    }
+
+    /// the first 4 bytes of `data`, if it is long enough
+    pub fn from_slice(data: &[u8]) -> Option<FourCC> {
+        data.get(..4)
+            .map(|code| FourCC([code[0], code[1], code[2], code[3]]))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 4] {
+        &self.0
+    }
+
+    /// the code as a little endian `u32`, like the `fcc_handler` and `biCompression` fields
+    pub fn to_u32(self) -> u32 {
+        u32::from_le_bytes(self.0)
+    }
+}
+
+impl From<[u8; 4]> for FourCC {
+    fn from(code: [u8; 4]) -> FourCC {
+        FourCC(code)
+    }
+}
+
+impl From<&[u8; 4]> for FourCC {
+    fn from(code: &[u8; 4]) -> FourCC {
+        FourCC(*code)
+    }
+}
+
+impl From<u32> for FourCC {
+    fn from(code: u32) -> FourCC {
+        FourCC(code.to_le_bytes())
+    }
+}
+
+impl AsRef<[u8]> for FourCC {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq<[u8; 4]> for FourCC {
+    fn eq(&self, other: &[u8; 4]) -> bool {
+        &self.0 == other
+    }
+}
+
+impl PartialEq<&[u8; 4]> for FourCC {
+    fn eq(&self, other: &&[u8; 4]) -> bool {
+        &self.0 == *other
+    }
+}
+
+/// printable ASCII characters as is, the other bytes escaped like `\x00`
+impl fmt::Display for FourCC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.0 {
+            if b.is_ascii_graphic() || b == b' ' {
+                write!(f, "{}", b as char)?;
+            } else {
+                write!(f, "\\x{:02x}", b)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FourCC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FourCC(\"{}\")", self)
+    }
+}
+
+/// the string is not 1 to 4 ASCII characters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFourCC;
+
+impl fmt::Display for InvalidFourCC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a fourcc is 1 to 4 ASCII characters")
+    }
+}
+
+impl std::error::Error for InvalidFourCC {}
+
+/// codes shorter than 4 characters are padded with spaces, like `AVI `
+impl FromStr for FourCC {
+    type Err = InvalidFourCC;
+
+    fn from_str(s: &str) -> Result<FourCC, InvalidFourCC> {
+        if s.is_empty() || s.len() > 4 || !s.is_ascii() {
+            return Err(InvalidFourCC);
+        }
+
+        let mut code = [b' '; 4];
+        code[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(FourCC(code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(FourCC::AVI.to_string(), "AVI ");
+        assert_eq!(FourCC::new(b"ix\x00\x01").to_string(), "ix\\x00\\x01");
+        assert_eq!(format!("{:?}", FourCC::MOVI), "FourCC(\"movi\")");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!("MP42".parse(), Ok(FourCC::new(b"MP42")));
+        assert_eq!("AVI".parse(), Ok(FourCC::AVI));
+        assert_eq!("".parse::<FourCC>(), Err(InvalidFourCC));
+        assert_eq!("DIVX5".parse::<FourCC>(), Err(InvalidFourCC));
+
+        let code = FourCC::from(0x3234_504d);
+        assert_eq!(code, *b"MP42");
+        assert_eq!(code.to_u32(), 0x3234_504d);
+        assert_eq!(FourCC::from_slice(b"00dc\x10"), Some(FourCC::new(b"00dc")));
+        assert_eq!(FourCC::from_slice(b"00"), None);
+    }
+}
@@ -0,0 +1,491 @@
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::{cond, map, map_parser},
+    error::{Error, ErrorKind},
+    multi::{count, many0},
+    number::complete::{le_u16, le_u32, le_u64, le_u8},
+    sequence::{terminated, tuple},
+    Err, IResult,
+};
+
+use std::cmp::min;
+
+use crate::fourcc::FourCC;
+use crate::parser::{chunk_kind, fourcc, stream_number, ChunkKind};
+
+/// the entry describes a `LIST` chunk (usually `rec `) instead of a data chunk
+pub const AVIIF_LIST: u32 = 0x0000_0001;
+/// the chunk can be decoded without referencing previous chunks
+pub const AVIIF_KEYFRAME: u32 = 0x0000_0010;
+/// the chunk does not affect the stream timing (palette changes, etc)
+pub const AVIIF_NO_TIME: u32 = 0x0000_0100;
+
+/// as seen on https://learn.microsoft.com/en-us/previous-versions/windows/desktop/api/aviriff/ns-aviriff-avioldindex
+#[derive(Debug, Clone, PartialEq)]
+pub struct Idx1Entry {
+    pub chunk_id: FourCC,
+    pub flags: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Idx1Entry {
+    pub fn is_keyframe(&self) -> bool {
+        self.flags & AVIIF_KEYFRAME != 0
+    }
+
+    pub fn is_list(&self) -> bool {
+        self.flags & AVIIF_LIST != 0
+    }
+
+    /// stream number from the two first characters of the chunk id
+    pub fn stream(&self) -> Option<u16> {
+        stream_number(self.chunk_id.as_bytes())
+    }
+}
+
+pub fn idx1_entry(input: &[u8]) -> IResult<&[u8], Idx1Entry> {
+    map(tuple((fourcc, le_u32, le_u32, le_u32)), |t| Idx1Entry {
+        chunk_id: t.0,
+        flags: t.1,
+        offset: t.2,
+        size: t.3,
+    })(input)
+}
+
+/// `idx1` chunk, including its tag and size
+///
+/// trailing bytes that do not make a full entry are ignored
+pub fn idx1(input: &[u8]) -> IResult<&[u8], Vec<Idx1Entry>> {
+    let (i, (_, size)) = tuple((tag(b"idx1"), le_u32))(input)?;
+
+    map_parser(terminated(take(size), take(size & 1)), many0(idx1_entry))(i)
+}
+
+/// what the `offset` field of `idx1` entries is relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idx1Base {
+    /// the position of the `movi` fourcc, as the specification says
+    Movi,
+    /// the beginning of the file, as written by some muxers
+    File,
+}
+
+/// guesses how the offsets of an `idx1` chunk are stored
+///
+/// `movi_offset` is the absolute position of the `movi` fourcc. A relative first
+/// offset always points before the `movi` list when taken as absolute.
+pub fn idx1_base(entries: &[Idx1Entry], movi_offset: u64) -> Idx1Base {
+    match entries.first() {
+        Some(entry) if entry.offset as u64 > movi_offset => Idx1Base::File,
+        _ => Idx1Base::Movi,
+    }
+}
+
+/// `bIndexType` of an index made of entries pointing to other indexes
+pub const AVI_INDEX_OF_INDEXES: u8 = 0x00;
+/// `bIndexType` of an index made of entries pointing to data chunks
+pub const AVI_INDEX_OF_CHUNKS: u8 = 0x01;
+/// `bIndexSubType` of a standard index with an offset for each field
+pub const AVI_INDEX_2FIELD: u8 = 0x01;
+
+/// the size field of standard index entries has this bit set for non keyframes
+pub(crate) const AVISTDINDEX_DELTAFRAME: u32 = 0x8000_0000;
+
+/// OpenDML `indx` or `ix##` chunk
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenDmlIndex {
+    Super(SuperIndex),
+    Standard(StandardIndex),
+}
+
+/// AVISUPERINDEX, lists the standard indexes of a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuperIndex {
+    pub index_sub_type: u8,
+    pub chunk_id: FourCC,
+    pub entries: Vec<SuperIndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuperIndexEntry {
+    /// absolute offset of the standard index chunk header
+    pub offset: u64,
+    /// size of the standard index chunk, header included
+    pub size: u32,
+    /// number of frames, or samples, covered by the standard index
+    pub duration: u32,
+}
+
+/// AVISTDINDEX, or AVIFIELDINDEX if the entries have a second field offset
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandardIndex {
+    pub index_sub_type: u8,
+    pub chunk_id: FourCC,
+    pub base_offset: u64,
+    pub entries: Vec<StandardIndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandardIndexEntry {
+    /// offset of the chunk payload, relative to `base_offset`
+    pub offset: u32,
+    /// size of the chunk payload, without the keyframe bit
+    pub size: u32,
+    pub keyframe: bool,
+    /// offset of the second field, relative to `base_offset`, for field indexes
+    pub second_field_offset: Option<u32>,
+}
+
+impl StandardIndex {
+    /// stream number from the two first characters of the indexed chunk id
+    pub fn stream(&self) -> Option<u16> {
+        stream_number(self.chunk_id.as_bytes())
+    }
+}
+
+impl SuperIndex {
+    pub fn stream(&self) -> Option<u16> {
+        stream_number(self.chunk_id.as_bytes())
+    }
+}
+
+/// `indx` or `ix##` chunk, including its tag and size
+///
+/// as seen in the OpenDML AVI File Format Extensions, version 1.02. The chunk can be
+/// larger than its entries, as muxers reserve space for super indexes.
+pub fn opendml_index(input: &[u8]) -> IResult<&[u8], OpenDmlIndex> {
+    let (i, (_, size)) = tuple((take(4usize), le_u32))(input)?;
+
+    map_parser(terminated(take(size), take(size & 1)), opendml_index_data)(i)
+}
+
+/// content of an `indx` or `ix##` chunk, after its tag and size
+pub fn opendml_index_data(input: &[u8]) -> IResult<&[u8], OpenDmlIndex> {
+    let (i, (longs_per_entry, index_sub_type, index_type, entries_in_use, chunk_id)) =
+        tuple((le_u16, le_u8, le_u8, le_u32, fourcc))(input)?;
+
+    match index_type {
+        AVI_INDEX_OF_INDEXES => map(
+            tuple((
+                take(12usize),
+                count(
+                    terminated(
+                        tuple((le_u64, le_u32, le_u32)),
+                        take(4 * longs_per_entry.saturating_sub(4) as usize),
+                    ),
+                    entries_in_use as usize,
+                ),
+            )),
+            |(_, entries)| {
+                OpenDmlIndex::Super(SuperIndex {
+                    index_sub_type,
+                    chunk_id,
+                    entries: entries
+                        .into_iter()
+                        .map(|(offset, size, duration)| SuperIndexEntry {
+                            offset,
+                            size,
+                            duration,
+                        })
+                        .collect(),
+                })
+            },
+        )(i),
+        AVI_INDEX_OF_CHUNKS => {
+            let fields = index_sub_type == AVI_INDEX_2FIELD;
+            let skipped = 4 * longs_per_entry.saturating_sub(if fields { 3 } else { 2 }) as usize;
+
+            map(
+                tuple((
+                    le_u64,
+                    le_u32,
+                    count(
+                        terminated(tuple((le_u32, le_u32, cond(fields, le_u32))), take(skipped)),
+                        entries_in_use as usize,
+                    ),
+                )),
+                |(base_offset, _, entries)| {
+                    OpenDmlIndex::Standard(StandardIndex {
+                        index_sub_type,
+                        chunk_id,
+                        base_offset,
+                        entries: entries
+                            .into_iter()
+                            .map(|(offset, size, second_field_offset)| StandardIndexEntry {
+                                offset,
+                                size: size & !AVISTDINDEX_DELTAFRAME,
+                                keyframe: size & AVISTDINDEX_DELTAFRAME == 0,
+                                second_field_offset,
+                            })
+                            .collect(),
+                    })
+                },
+            )(i)
+        }
+        _ => Err(Err::Error(Error::new(input, ErrorKind::Switch))),
+    }
+}
+
+/// a chunk position, as stored in the indexes
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    /// absolute offset of the chunk header
+    pub offset: u64,
+    /// size of the chunk payload
+    pub size: u32,
+    pub keyframe: bool,
+    /// palette changes are indexed along with the frames of their stream
+    pub kind: ChunkKind,
+}
+
+/// the chunks of one stream, in file order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+impl StreamIndex {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// position of the last keyframe at or before the `position`-th chunk
+    pub fn keyframe_before(&self, position: usize) -> Option<usize> {
+        let end = min(position + 1, self.entries.len());
+
+        self.entries[..end].iter().rposition(|e| e.keyframe)
+    }
+
+    /// position of the first keyframe at or after the `position`-th chunk
+    pub fn keyframe_after(&self, position: usize) -> Option<usize> {
+        self.entries
+            .iter()
+            .skip(position)
+            .position(|e| e.keyframe)
+            .map(|p| p + position)
+    }
+}
+
+/// per stream chunk positions, indexed by stream number
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Index {
+    pub streams: Vec<StreamIndex>,
+}
+
+impl Index {
+    pub fn stream(&self, stream: u16) -> Option<&StreamIndex> {
+        self.streams.get(stream as usize)
+    }
+
+    pub fn stream_mut(&mut self, stream: u16) -> &mut StreamIndex {
+        let stream = stream as usize;
+        if self.streams.len() <= stream {
+            self.streams.resize_with(stream + 1, StreamIndex::default);
+        }
+
+        &mut self.streams[stream]
+    }
+
+    /// builds the index from `idx1` entries
+    ///
+    /// `movi_offset` is the absolute position of the `movi` fourcc. `LIST` entries and
+    /// chunks not belonging to a stream are left out.
+    pub fn from_idx1(entries: &[Idx1Entry], movi_offset: u64) -> Index {
+        let base = match idx1_base(entries, movi_offset) {
+            Idx1Base::Movi => movi_offset,
+            Idx1Base::File => 0,
+        };
+
+        let mut index = Index::default();
+        for entry in entries.iter().filter(|e| !e.is_list()) {
+            if let Some(stream) = entry.stream() {
+                index.stream_mut(stream).entries.push(IndexEntry {
+                    offset: base + entry.offset as u64,
+                    size: entry.size,
+                    keyframe: entry.is_keyframe(),
+                    kind: chunk_kind(entry.chunk_id.as_bytes()),
+                });
+            }
+        }
+
+        index
+    }
+
+    /// adds the entries of an OpenDML standard index
+    pub fn add_standard_index(&mut self, index: &StandardIndex) {
+        if let Some(stream) = index.stream() {
+            let entries = &mut self.stream_mut(stream).entries;
+            entries.extend(index.entries.iter().map(|e| IndexEntry {
+                // the standard index points to the payload, after the chunk header
+                offset: (index.base_offset + e.offset as u64).saturating_sub(8),
+                size: e.size,
+                keyframe: e.keyframe,
+                kind: chunk_kind(index.chunk_id.as_bytes()),
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use super::*;
+
+    const drop: &[u8] = include_bytes!("../assets/drop.avi");
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    #[test]
+    fn parse_idx1() {
+        let (_, entries) = idx1(&drop[672716..]).unwrap();
+        println!("first entries: {:?}", &entries[..4]);
+        assert_eq!(entries.len(), 182);
+        assert_eq!(
+            entries[0],
+            Idx1Entry {
+                chunk_id: FourCC::new(b"00db"),
+                flags: AVIIF_KEYFRAME,
+                offset: 4,
+                size: 2686,
+            }
+        );
+        assert_eq!(idx1_base(&entries, 2044), Idx1Base::Movi);
+
+        let index = Index::from_idx1(&entries, 2044);
+        assert_eq!(index.streams.len(), 1);
+        let first = &index.streams[0].entries[0];
+        assert_eq!(first.offset, 2048);
+        assert_eq!(
+            &drop[first.offset as usize..first.offset as usize + 4],
+            b"00db"
+        );
+    }
+
+    #[test]
+    fn parse_idx1_streams() {
+        let (_, entries) = idx1(&verona[1913316..]).unwrap();
+        let index = Index::from_idx1(&entries, 2044);
+        assert_eq!(index.stream(0).map(|s| s.len()), Some(772));
+        assert_eq!(index.stream(1).map(|s| s.len()), Some(62));
+
+        let video = index.stream(0).unwrap();
+        for entry in video
+            .entries
+            .iter()
+            .chain(&index.stream(1).unwrap().entries)
+        {
+            let offset = entry.offset as usize;
+            assert_eq!(&verona[offset + 4..offset + 8], &entry.size.to_le_bytes());
+        }
+
+        assert_eq!(video.keyframe_before(0), Some(0));
+        assert!(!video.entries[1].keyframe);
+        let next = video.keyframe_after(1).unwrap();
+        assert!(video.entries[next].keyframe);
+        assert_eq!(video.keyframe_before(next), Some(next));
+        assert_eq!(video.keyframe_before(next - 1), Some(0));
+    }
+
+    #[test]
+    fn parse_super_index() {
+        let mut data = b"indx".to_vec();
+        data.extend_from_slice(&56u32.to_le_bytes());
+        data.extend_from_slice(&[4, 0, 0, AVI_INDEX_OF_INDEXES]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(b"00dc");
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&0x1_2345_6780u64.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        // reserved space for more entries
+        data.extend_from_slice(&[0; 16]);
+
+        let (i, index) = opendml_index(&data).unwrap();
+        assert!(i.is_empty());
+        assert_eq!(
+            index,
+            OpenDmlIndex::Super(SuperIndex {
+                index_sub_type: 0,
+                chunk_id: FourCC::new(b"00dc"),
+                entries: vec![SuperIndexEntry {
+                    offset: 0x1_2345_6780,
+                    size: 40,
+                    duration: 2,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn parse_standard_index() {
+        let mut data = b"ix01".to_vec();
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&[2, 0, 0, AVI_INDEX_OF_CHUNKS]);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"01wb");
+        data.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(&116u32.to_le_bytes());
+        data.extend_from_slice(&(0x8000_0000u32 | 200).to_le_bytes());
+
+        let (_, index) = opendml_index(&data).unwrap();
+        let standard = match index {
+            OpenDmlIndex::Standard(s) => s,
+            i => panic!("unexpected index: {:?}", i),
+        };
+        assert_eq!(standard.stream(), Some(1));
+        assert!(standard.entries[0].keyframe);
+        assert!(!standard.entries[1].keyframe);
+        assert_eq!(standard.entries[1].size, 200);
+
+        let mut index = Index::default();
+        index.add_standard_index(&standard);
+        assert!(index.stream(0).unwrap().is_empty());
+        assert_eq!(
+            index.stream(1).unwrap().entries,
+            vec![
+                IndexEntry {
+                    offset: 0x1_0000_0000,
+                    size: 100,
+                    keyframe: true,
+                    kind: ChunkKind::Audio,
+                },
+                IndexEntry {
+                    offset: 0x1_0000_006c,
+                    size: 200,
+                    keyframe: false,
+                    kind: ChunkKind::Audio,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_field_index() {
+        let mut data = b"ix00".to_vec();
+        data.extend_from_slice(&36u32.to_le_bytes());
+        data.extend_from_slice(&[3, 0, AVI_INDEX_2FIELD, AVI_INDEX_OF_CHUNKS]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(b"00dc");
+        data.extend_from_slice(&4096u64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.extend_from_slice(&508u32.to_le_bytes());
+
+        let (_, index) = opendml_index(&data).unwrap();
+        match index {
+            OpenDmlIndex::Standard(s) => {
+                assert_eq!(s.entries[0].second_field_offset, Some(508));
+                assert_eq!(s.entries[0].size, 1000);
+            }
+            i => panic!("unexpected index: {:?}", i),
+        }
+    }
+}
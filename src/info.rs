@@ -0,0 +1,273 @@
+use std::io::{self, Write};
+
+use nom::{
+    bytes::complete::{take, take_till},
+    combinator::{map, rest},
+    multi::many0,
+    number::complete::le_u32,
+    sequence::{terminated, tuple},
+    IResult,
+};
+
+use crate::fourcc::FourCC;
+use crate::parser::{fourcc, to_bytes};
+
+/// the well known `LIST INFO` subchunks, as seen on
+/// <https://www.exiftool.org/TagNames/RIFF.html#Info>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InfoTag {
+    /// `INAM`
+    Title,
+    /// `IART`
+    Artist,
+    /// `ICMT`
+    Comment,
+    /// `ICRD`, usually like `2004-05-21`
+    CreationDate,
+    /// `ISFT`
+    Software,
+    /// `IGNR`
+    Genre,
+    /// `ICOP`
+    Copyright,
+    /// `ISRC`, the person or organization that supplied the content
+    Source,
+    /// `IPRD`, the product, like an album, the content is part of
+    Product,
+    /// `IENG`
+    Engineer,
+    /// `ISBJ`
+    Subject,
+    /// `IKEY`, separated by semicolons
+    Keywords,
+    /// `ILNG`
+    Language,
+    /// `IMED`, the original medium, like `VHS`
+    Medium,
+    /// `ITCH`, the person who digitized the content
+    Technician,
+    /// `ICMS`, who commissioned the content
+    Commissioned,
+    /// `IPRT`, like `1/12`
+    Part,
+    Other(FourCC),
+}
+
+const INFO_TAGS: &[(InfoTag, &[u8; 4])] = &[
+    (InfoTag::Title, b"INAM"),
+    (InfoTag::Artist, b"IART"),
+    (InfoTag::Comment, b"ICMT"),
+    (InfoTag::CreationDate, b"ICRD"),
+    (InfoTag::Software, b"ISFT"),
+    (InfoTag::Genre, b"IGNR"),
+    (InfoTag::Copyright, b"ICOP"),
+    (InfoTag::Source, b"ISRC"),
+    (InfoTag::Product, b"IPRD"),
+    (InfoTag::Engineer, b"IENG"),
+    (InfoTag::Subject, b"ISBJ"),
+    (InfoTag::Keywords, b"IKEY"),
+    (InfoTag::Language, b"ILNG"),
+    (InfoTag::Medium, b"IMED"),
+    (InfoTag::Technician, b"ITCH"),
+    (InfoTag::Commissioned, b"ICMS"),
+    (InfoTag::Part, b"IPRT"),
+];
+
+impl InfoTag {
+    pub fn from_fourcc(fourcc: FourCC) -> InfoTag {
+        INFO_TAGS
+            .iter()
+            .find(|(_, code)| fourcc == **code)
+            .map_or(InfoTag::Other(fourcc), |(tag, _)| *tag)
+    }
+
+    pub fn fourcc(&self) -> FourCC {
+        match self {
+            InfoTag::Other(fourcc) => *fourcc,
+            tag => INFO_TAGS
+                .iter()
+                .find(|(t, _)| t == tag)
+                .map(|(_, code)| FourCC::new(code))
+                .expect("every tag is listed"),
+        }
+    }
+}
+
+/// the text entries of the `LIST INFO` chunks, in file order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    entries: Vec<(InfoTag, String)>,
+}
+
+impl Metadata {
+    pub fn get(&self, tag: InfoTag) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// sets the value of `tag`, keeping its position if it was already set
+    pub fn insert(&mut self, tag: InfoTag, value: impl Into<String>) {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(t, _)| *t == tag) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((tag, value)),
+        }
+    }
+
+    pub fn remove(&mut self, tag: InfoTag) -> Option<String> {
+        let position = self.entries.iter().position(|(t, _)| *t == tag)?;
+        Some(self.entries.remove(position).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (InfoTag, &str)> {
+        self.entries
+            .iter()
+            .map(|(tag, value)| (*tag, value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// adds the entries of another `INFO` list, the values of `other` replacing ours
+    pub fn extend(&mut self, other: Metadata) {
+        for (tag, value) in other.entries {
+            self.insert(tag, value);
+        }
+    }
+
+    /// writes the `INFO` list content, after the list header: the `INFO` fourcc then a
+    /// null terminated UTF-8 string chunk per entry
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(FourCC::INFO.as_bytes())?;
+        for (tag, value) in &self.entries {
+            let size = value.len() as u32 + 1;
+            w.write_all(tag.fourcc().as_bytes())?;
+            w.write_all(&size.to_le_bytes())?;
+            w.write_all(value.as_bytes())?;
+            // the null terminator, and the padding byte of odd sizes
+            w.write_all(&[0; 2][..1 + (size & 1) as usize])?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+}
+
+/// content of a `LIST INFO` chunk, after the `INFO` fourcc
+pub fn info(input: &[u8]) -> IResult<&[u8], Metadata> {
+    map(many0(info_entry), |entries| Metadata {
+        entries: entries
+            .into_iter()
+            .filter(|(_, value): &(InfoTag, String)| !value.is_empty())
+            .collect(),
+    })(input)
+}
+
+fn info_entry(input: &[u8]) -> IResult<&[u8], (InfoTag, String)> {
+    let (i, (code, size)) = tuple((fourcc, le_u32))(input)?;
+    // some muxers forget the padding byte of the last entry
+    let padding = (size as usize & 1).min(i.len().saturating_sub(size as usize));
+    let (i, value) = terminated(take(size), take(padding))(i)?;
+    let (_, text) = terminated(take_till(|b| b == 0), rest)(value)?;
+
+    Ok((i, (InfoTag::from_fourcc(code), decode_text(text))))
+}
+
+/// the encoding is not specified, UTF-8 is used when valid and Windows-1252, as most
+/// Windows muxers write, otherwise
+fn decode_text(text: &[u8]) -> String {
+    match std::str::from_utf8(text) {
+        Ok(s) => s.trim_end().to_string(),
+        Err(_) => text
+            .iter()
+            .map(|&b| windows_1252(b))
+            .collect::<String>()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+/// the 0x80 - 0x9f range differs from Latin-1, undefined bytes are kept as control codes
+fn windows_1252(b: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+
+    match b {
+        0x80..=0x9f => HIGH[(b - 0x80) as usize],
+        b => b as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(code: &[u8; 4], value: &[u8]) -> Vec<u8> {
+        let mut data = code.to_vec();
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value);
+        if value.len() % 2 == 1 {
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn parse_info() {
+        let data = [
+            entry(b"INAM", b"Verona\0"),
+            entry(b"ISFT", b"Lavf58.29.100\0"),
+            entry(b"ICMT", b"caf\xe9 \x80\0"),
+            entry(b"IXYZ", b"other\0"),
+            entry(b"IART", b"\0"),
+        ]
+        .concat();
+
+        let (i, metadata) = info(&data).unwrap();
+        assert!(i.is_empty());
+        assert_eq!(metadata.get(InfoTag::Title), Some("Verona"));
+        assert_eq!(metadata.get(InfoTag::Software), Some("Lavf58.29.100"));
+        assert_eq!(metadata.get(InfoTag::Comment), Some("café €"));
+        assert_eq!(
+            metadata.get(InfoTag::Other(FourCC::new(b"IXYZ"))),
+            Some("other")
+        );
+        assert_eq!(metadata.get(InfoTag::Artist), None);
+        assert_eq!(metadata.len(), 4);
+    }
+
+    #[test]
+    fn write_info() {
+        let mut metadata = Metadata::default();
+        metadata.insert(InfoTag::Title, "Verona");
+        metadata.insert(InfoTag::Copyright, "CC BY ©");
+        metadata.insert(InfoTag::Title, "Verona 60");
+        assert_eq!(metadata.remove(InfoTag::Genre), None);
+
+        let data = metadata.to_bytes();
+        assert_eq!(&data[..4], b"INFO");
+        assert_eq!(&data[4..12], b"INAM\x0a\0\0\0");
+        assert_eq!(data.len() % 2, 0);
+        let (_, parsed) = info(&data[4..]).unwrap();
+        assert_eq!(parsed, metadata);
+        assert_eq!(
+            parsed.iter().collect::<Vec<_>>(),
+            vec![
+                (InfoTag::Title, "Verona 60"),
+                (InfoTag::Copyright, "CC BY ©")
+            ]
+        );
+    }
+}
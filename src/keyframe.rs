@@ -0,0 +1,157 @@
+use crate::codec::CodecId;
+use crate::fourcc::FourCC;
+use crate::parser::ChunkKind;
+use crate::state::StreamContext;
+
+/// how keyframes are told apart from the chunk data, for files without an index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyframeHeuristic {
+    /// audio, subtitles and intra only video codecs like MJPEG, HuffYUV or DV
+    All,
+    /// MPEG-4 part 2, the first VOP is an I-VOP
+    Mpeg4,
+    /// Microsoft MPEG-4 v2 and v3, the picture type is in the first two bits
+    MsMpeg4,
+    /// H.264, the frame has an IDR slice
+    H264,
+    /// unknown codecs, only the first chunk is assumed to be a keyframe
+    First,
+}
+
+impl KeyframeHeuristic {
+    pub fn new(stream: &StreamContext) -> KeyframeHeuristic {
+        match stream {
+            StreamContext::Video(_) => KeyframeHeuristic::from_codec(stream.codec()),
+            _ => KeyframeHeuristic::All,
+        }
+    }
+
+    /// heuristic for a video codec fourcc
+    pub fn from_fourcc(fourcc: FourCC) -> KeyframeHeuristic {
+        KeyframeHeuristic::from_codec(CodecId::from_fourcc(fourcc))
+    }
+
+    pub fn from_codec(codec: CodecId) -> KeyframeHeuristic {
+        match codec {
+            CodecId::RawVideo
+            | CodecId::Mjpeg
+            | CodecId::Ljpeg
+            | CodecId::JpegLs
+            | CodecId::Png
+            | CodecId::HuffYuv
+            | CodecId::Ffv1
+            | CodecId::Lagarith
+            | CodecId::Dv => KeyframeHeuristic::All,
+            CodecId::Mpeg4 => KeyframeHeuristic::Mpeg4,
+            CodecId::MsMpeg4v2 | CodecId::MsMpeg4v3 => KeyframeHeuristic::MsMpeg4,
+            CodecId::H264 => KeyframeHeuristic::H264,
+            _ => KeyframeHeuristic::First,
+        }
+    }
+
+    /// whether a chunk is a keyframe, from the beginning of its data
+    ///
+    /// `first` tells if this is the first chunk of the stream. Empty video chunks are
+    /// dropped frames, and `##db` chunks uncompressed frames.
+    pub fn is_keyframe(&self, kind: ChunkKind, data: &[u8], first: bool) -> bool {
+        match kind {
+            ChunkKind::UncompressedVideo => return true,
+            ChunkKind::CompressedVideo if data.is_empty() => return false,
+            ChunkKind::CompressedVideo => {}
+            _ => return true,
+        }
+
+        match self {
+            KeyframeHeuristic::All => true,
+            KeyframeHeuristic::Mpeg4 => mpeg4_keyframe(data),
+            KeyframeHeuristic::MsMpeg4 => data[0] >> 6 == 0,
+            KeyframeHeuristic::H264 => h264_keyframe(data),
+            KeyframeHeuristic::First => first,
+        }
+    }
+}
+
+/// the first VOP start code is followed by the coding type, 0 for intra
+fn mpeg4_keyframe(data: &[u8]) -> bool {
+    data.windows(5)
+        .find(|w| w[..4] == [0, 0, 1, 0xb6])
+        .is_some_and(|w| w[4] >> 6 == 0)
+}
+
+/// NAL units are either in Annex B format, separated by start codes, or prefixed by
+/// their size on 4 bytes like in MP4
+fn h264_keyframe(data: &[u8]) -> bool {
+    let annex_b = data
+        .windows(4)
+        .filter(|w| w[..3] == [0, 0, 1])
+        .map(|w| w[3] & 0x1f);
+    if let Some(idr) = idr_slice(annex_b) {
+        return idr;
+    }
+
+    let mut i = data;
+    let length_prefixed = std::iter::from_fn(|| {
+        let len = u32::from_be_bytes(i.get(..4)?.try_into().ok()?) as usize;
+        let nal_type = i.get(4)? & 0x1f;
+        i = i.get(4 + len..).unwrap_or(&[]);
+        Some(nal_type)
+    });
+    idr_slice(length_prefixed).unwrap_or(false)
+}
+
+/// whether the first slice in the NAL unit types is an IDR one
+fn idr_slice(mut nal_types: impl Iterator<Item = u8>) -> Option<bool> {
+    nal_types.find(|t| (1..=5).contains(t)).map(|t| t == 5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heuristics() {
+        assert_eq!(
+            KeyframeHeuristic::from_fourcc(FourCC::new(b"xvid")),
+            KeyframeHeuristic::Mpeg4
+        );
+        assert_eq!(
+            KeyframeHeuristic::from_fourcc(FourCC::new(b"avc1")),
+            KeyframeHeuristic::H264
+        );
+        assert_eq!(
+            KeyframeHeuristic::from_fourcc(crate::parser::BI_RGB),
+            KeyframeHeuristic::All
+        );
+        assert_eq!(
+            KeyframeHeuristic::from_fourcc(FourCC::new(b"IV41")),
+            KeyframeHeuristic::First
+        );
+    }
+
+    #[test]
+    fn keyframes() {
+        let video = ChunkKind::CompressedVideo;
+        let mpeg4 = KeyframeHeuristic::Mpeg4;
+        assert!(mpeg4.is_keyframe(video, &[0, 0, 1, 0xb0, 1, 0, 0, 1, 0xb6, 0x10], false));
+        assert!(!mpeg4.is_keyframe(video, &[0, 0, 1, 0xb6, 0x50], false));
+        assert!(!mpeg4.is_keyframe(video, &[], true));
+
+        let h264 = KeyframeHeuristic::H264;
+        // SPS, PPS then an IDR slice
+        assert!(h264.is_keyframe(
+            video,
+            &[0, 0, 0, 1, 0x67, 0, 0, 1, 0x68, 0, 0, 1, 0x65],
+            false
+        ));
+        assert!(!h264.is_keyframe(video, &[0, 0, 0, 1, 0x41, 0x9a], false));
+        assert!(h264.is_keyframe(
+            video,
+            &[0, 0, 0, 2, 0x09, 0x10, 0, 0, 0, 2, 0x65, 0x88],
+            false
+        ));
+
+        assert!(KeyframeHeuristic::First.is_keyframe(video, &[1], true));
+        assert!(!KeyframeHeuristic::First.is_keyframe(video, &[1], false));
+        assert!(KeyframeHeuristic::First.is_keyframe(ChunkKind::Audio, &[1], false));
+    }
+}
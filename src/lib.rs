@@ -1,5 +1,28 @@
+pub mod codec;
+pub mod editor;
+pub mod error;
+pub mod fourcc;
+pub mod index;
+pub mod info;
+pub mod keyframe;
 pub mod parser;
+pub mod reader;
 pub mod state;
+pub mod time;
+pub mod writer;
 
+pub use codec::*;
+pub use editor::*;
+pub use error::*;
+pub use fourcc::*;
+pub use index::*;
+pub use info::*;
+pub use keyframe::*;
 pub use parser::*;
+pub use reader::*;
 pub use state::*;
+pub use time::*;
+pub use writer::*;
+
+// both modules define a `List`, the parser's one is the public facing type
+pub use parser::List;
@@ -1,17 +1,65 @@
 use nom::{
+    bits::{bits, complete::take as bits_take},
     branch::alt,
-    bytes::complete::{tag, take},
-    combinator::{map, verify},
+    bytes::complete::{tag, take, take_till},
+    combinator::{map, map_opt, map_parser, opt, rest, verify},
+    error::Error,
+    multi::{length_count, many0},
     number::complete::{le_i16, le_i32, le_u16, le_u32},
-    sequence::tuple,
-    IResult,
+    sequence::{terminated, tuple},
+    IResult, Offset,
 };
+use std::cmp::min;
+use std::io::{self, Write};
+
+use crate::fourcc::FourCC;
+use crate::index::{idx1_entry, opendml_index_data, Idx1Entry, OpenDmlIndex};
+use crate::info::{info, Metadata};
+
+/// serializes with a `write_to()` method, writing to a `Vec` cannot fail
+pub(crate) fn to_bytes(write_to: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_to(&mut data).expect("writing to a Vec");
+    data
+}
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Header<'a> {
-    magic1: &'a [u8],
+pub struct Header {
+    magic1: FourCC,
     pub file_size: u32,
-    magic2: &'a [u8],
+    magic2: FourCC,
+}
+
+impl Header {
+    /// `RIFF` header of `file_size` bytes, `form_type` being `AVI ` or `AVIX`
+    pub fn new(file_size: u32, form_type: FourCC) -> Header {
+        Header {
+            magic1: FourCC::RIFF,
+            file_size,
+            magic2: form_type,
+        }
+    }
+
+    /// `RIFF`, or `ON2 ` for On2 files
+    pub fn magic(&self) -> FourCC {
+        self.magic1
+    }
+
+    /// `AVI `, `AVIX` for the OpenDML extension chunks, or a variant like `AMV `
+    pub fn form_type(&self) -> FourCC {
+        self.magic2
+    }
+
+    /// writes the 12 bytes parsed by `header()`
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(self.magic1.as_bytes())?;
+        w.write_all(&self.file_size.to_le_bytes())?;
+        w.write_all(self.magic2.as_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
 }
 
 pub fn header(input: &[u8]) -> IResult<&[u8], Header> {
@@ -24,32 +72,94 @@ pub fn header(input: &[u8]) -> IResult<&[u8], Header> {
             )),
             tuple((tag(b"ON2 "), le_u32, tag(b"ON2f"))),
         )),
-        |(magic1, file_size, magic2)| Header {
-            magic1,
+        |(magic1, file_size, magic2): (&[u8], u32, &[u8])| Header {
+            magic1: to_fourcc(magic1),
             file_size,
-            magic2,
+            magic2: to_fourcc(magic2),
+        },
+    )(input)
+}
+
+/// header of the `RIFF AVIX` chunks following the first one in OpenDML files
+///
+/// this one is streaming: it returns `Incomplete` if the input ends before the header,
+/// since the state machine cannot tell the end of the file from a short read
+pub fn extension_header(input: &[u8]) -> IResult<&[u8], Header> {
+    use nom::{bytes::streaming::tag, number::streaming::le_u32};
+
+    map(
+        tuple((tag(b"RIFF"), le_u32, tag(b"AVIX"))),
+        |(magic1, file_size, magic2): (&[u8], u32, &[u8])| Header {
+            magic1: to_fourcc(magic1),
+            file_size,
+            magic2: to_fourcc(magic2),
         },
     )(input)
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct BlockHeader<'a> {
-    tag: &'a [u8],
+pub struct BlockHeader {
+    tag: FourCC,
     size: u32,
 }
 
+impl BlockHeader {
+    pub fn new(tag: FourCC, size: u32) -> BlockHeader {
+        BlockHeader { tag, size }
+    }
+
+    pub fn tag(&self) -> FourCC {
+        self.tag
+    }
+
+    /// size of the chunk content, without the header and the padding byte
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// writes the 8 bytes parsed by `block_header()`
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(self.tag.as_bytes())?;
+        w.write_all(&self.size.to_le_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+}
+
 pub fn block_header(input: &[u8]) -> IResult<&[u8], BlockHeader> {
-    map(tuple((take(4usize), le_u32)), |(tag, size)| BlockHeader {
+    map(tuple((fourcc, le_u32)), |(tag, size)| BlockHeader {
         tag,
         size,
     })(input)
 }
 
+pub fn fourcc(input: &[u8]) -> IResult<&[u8], FourCC> {
+    map(take(4usize), to_fourcc)(input)
+}
+
+/// for the 4 bytes slices returned by the parsers
+fn to_fourcc(code: &[u8]) -> FourCC {
+    FourCC([code[0], code[1], code[2], code[3]])
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Block {
     List(usize, List),
     Avih(MainAVIHeader),
+    /// a whole `LIST INFO`
+    Info(Metadata),
+    Dmlh(ExtendedAVIHeader),
     Strh(AVIStreamHeader),
+    /// stream name
+    Strn(String),
+    /// codec driver data
+    Strd(Vec<u8>),
+    Vprp(VideoPropHeader),
+    Idx1(Vec<Idx1Entry>),
+    OpenDmlIndex(OpenDmlIndex),
+    Junk,
     Unimplemented,
     Default,
 }
@@ -58,18 +168,24 @@ pub enum Block {
 pub enum List {
     Hdrl,
     Movi(usize),
+    /// groups data chunks that should be read at once, inside `movi`
+    Rec,
+    /// text metadata
+    Info,
     Default,
-    Unknown(Vec<u8>),
+    Unknown(FourCC),
 }
 
 pub fn list(
     input: &[u8],
     stream_offset: usize,
-    file_size: u32,
+    riff_end: usize,
     list_size: u32,
 ) -> IResult<&[u8], List> {
+    use nom::bytes::streaming::take;
+
     map(take(4usize), |val: &[u8]| match val {
-        b"INFO" => List::Default,
+        b"INFO" => List::Info,
         b"ncdt" => List::Default,
         b"movi" => {
             if list_size != 0 {
@@ -80,47 +196,346 @@ pub fn list(
                 // FIXME: check for overflow
                 List::Movi(offset + list_size as usize + (list_size & 1) as usize)
             } else {
-                List::Movi(file_size as usize)
+                // unfinished captures leave the size at 0, the list spans the whole RIFF
+                List::Movi(riff_end)
             }
         }
         b"hdrl" => List::Hdrl,
-        a => List::Unknown(a.to_owned()),
+        b"rec " => List::Rec,
+        a => List::Unknown(to_fourcc(a)),
     })(input)
 }
 
 /// block()
 ///
-/// stream_offset is the offset corresponding to the position of `input` from the beginning of the stream
-pub fn block(input: &[u8], stream_offset: usize, file_size: u32) -> IResult<&[u8], Block> {
+/// stream_offset is the offset corresponding to the position of `input` from the beginning of the stream,
+/// riff_end is the offset of the end of the current `RIFF` chunk
+///
+/// this one is streaming: the chunks we decode are only parsed once their whole content is
+/// available. Lists stop after their type, and the other chunks after their header, the caller
+/// skipping their content
+pub fn block(input: &[u8], stream_offset: usize, riff_end: usize) -> IResult<&[u8], Block> {
+    use nom::{bytes::streaming::take, number::streaming::le_u32};
+
     tuple((take(4usize), le_u32))(input).and_then(|(i, (tag, size))| match tag {
-        b"LIST" => {
-            list(i, stream_offset, file_size, size).map(|(i, l)| (i, Block::List(size as usize, l)))
-        }
+        b"LIST" => match list(i, stream_offset, riff_end, size)? {
+            // the list size counts the list type
+            (i, List::Info) => map(chunk_data(size.saturating_sub(4), info), Block::Info)(i),
+            (i, l) => Ok((i, Block::List(size as usize, l))),
+        },
         b"IDIT" => Ok((i, Block::Unimplemented)),
-        b"dmlh" => Ok((i, Block::Unimplemented)),
+        b"dmlh" => map(chunk_data(size, dmlh), Block::Dmlh)(i),
         b"amvh" => Ok((i, Block::Unimplemented)),
-        b"avih" => map(avih, Block::Avih)(i),
-        b"strh" => map(strh, Block::Strh)(i),
+        b"avih" => map(chunk_data(size, avih), Block::Avih)(i),
+        b"strh" => map(chunk_data(size, strh), Block::Strh)(i),
         b"strf" => Ok((i, Block::Unimplemented)),
-        b"indx" => Ok((i, Block::Unimplemented)),
-        b"vprp" => Ok((i, Block::Unimplemented)),
-        b"strn" => Ok((i, Block::Unimplemented)),
+        b"indx" | [b'i', b'x', _, _] => {
+            map(chunk_data(size, opendml_index_data), Block::OpenDmlIndex)(i)
+        }
+        b"vprp" => map(chunk_data(size, vprp), Block::Vprp)(i),
+        b"strn" => map(chunk_data(size, strn), Block::Strn)(i),
+        b"strd" => map(chunk_data(size, rest), |data: &[u8]| {
+            Block::Strd(data.to_vec())
+        })(i),
+        b"idx1" => map(chunk_data(size, many0(idx1_entry)), Block::Idx1)(i),
+        b"JUNK" | b"JUNQ" | b"PAD " => Ok((i, Block::Junk)),
         _ => Ok((i, Block::Default)),
     })
 }
 
+/// applies `parser` to the `size` bytes of a chunk content, once they are all available,
+/// and consumes the padding byte following odd sized chunks
+fn chunk_data<'a, O, F>(size: u32, parser: F) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], O>
+where
+    F: FnMut(&'a [u8]) -> IResult<&'a [u8], O>,
+{
+    use nom::bytes::streaming::take;
+
+    map_parser(terminated(take(size), take(size & 1)), parser)
+}
+
+/// stream number from the two first characters of a chunk id, like `01` in `01wb`
+pub fn stream_number(chunk_id: &[u8]) -> Option<u16> {
+    match chunk_id {
+        [a @ b'0'..=b'9', b @ b'0'..=b'9', ..] => Some(((a - b'0') * 10 + (b - b'0')) as u16),
+        _ => None,
+    }
+}
+
+/// what a data chunk holds, from the two last characters of its id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    /// `dc`
+    CompressedVideo,
+    /// `db`
+    UncompressedVideo,
+    /// `wb`
+    Audio,
+    /// `tx`
+    Subtitle,
+    /// `pc`, a new palette for the following video frames
+    PaletteChange,
+    Unknown([u8; 2]),
+}
+
+pub fn chunk_kind(chunk_id: &[u8]) -> ChunkKind {
+    match &chunk_id[2..4] {
+        b"dc" => ChunkKind::CompressedVideo,
+        b"db" => ChunkKind::UncompressedVideo,
+        b"wb" => ChunkKind::Audio,
+        b"tx" => ChunkKind::Subtitle,
+        b"pc" => ChunkKind::PaletteChange,
+        k => ChunkKind::Unknown([k[0], k[1]]),
+    }
+}
+
+/// header of a stream data chunk, like `00dc` or `01wb`
 #[derive(Debug, Clone, PartialEq)]
+pub struct DataChunk {
+    /// absolute offset of the chunk header, the data follows it
+    pub offset: usize,
+    pub tag: FourCC,
+    pub stream: u16,
+    pub kind: ChunkKind,
+    /// size of the chunk content, without the header and padding
+    pub size: u32,
+}
+
+/// data chunk header, `offset` being the absolute position of `input`
+///
+/// fails on chunk ids not starting with a stream number
+pub fn data_chunk(input: &[u8], offset: usize) -> IResult<&[u8], DataChunk> {
+    map_opt(
+        tuple((take(4usize), le_u32)),
+        |(tag, size): (&[u8], u32)| {
+            stream_number(tag).map(|stream| DataChunk {
+                offset,
+                tag: to_fourcc(tag),
+                stream,
+                kind: chunk_kind(tag),
+                size,
+            })
+        },
+    )(input)
+}
+
+/// data chunks of a `movi` list held in memory, with their content
+///
+/// `input` starts after the `movi` fourcc, at the absolute position `offset`. The `rec `
+/// lists are flattened, index and padding chunks are left out. A chunk larger than the
+/// remaining data ends the list.
+pub fn movi_data(input: &[u8], offset: usize) -> IResult<&[u8], Vec<(DataChunk, &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut i = input;
+
+    while i.len() >= 8 {
+        let position = offset + input.offset(i);
+        let (content, (tag, size)) = tuple((take(4usize), le_u32))(i)?;
+        let size = size as usize;
+
+        if tag == b"LIST" {
+            // the list size counts the list type
+            let (rest, list) = take(min(size, content.len()))(content)?;
+            if let Some(rec) = list.strip_prefix(b"rec ") {
+                let (_, mut rec_chunks) = movi_data(rec, position + 12)?;
+                chunks.append(&mut rec_chunks);
+            }
+            i = rest;
+            continue;
+        }
+
+        if size > content.len() {
+            break;
+        }
+        let (rest, data) = take(size)(content)?;
+        if let Ok((_, chunk)) = data_chunk(i, position) {
+            chunks.push((chunk, data));
+        }
+        i = &rest[min(size & 1, rest.len())..];
+    }
+
+    Ok((i, chunks))
+}
+
+/// `avih` flags, as seen on
+/// https://learn.microsoft.com/en-us/previous-versions/windows/desktop/api/aviriff/ns-aviriff-avimainheader
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AviFlags(pub u32);
+
+/// the file has an `idx1` index
+pub const AVIF_HASINDEX: AviFlags = AviFlags(0x0000_0010);
+/// the chunks should be read in the index order instead of the file order
+pub const AVIF_MUSTUSEINDEX: AviFlags = AviFlags(0x0000_0020);
+pub const AVIF_ISINTERLEAVED: AviFlags = AviFlags(0x0000_0100);
+/// the keyframe flags of the index can be trusted
+pub const AVIF_TRUSTCKTYPE: AviFlags = AviFlags(0x0000_0800);
+/// the file was allocated for capture
+pub const AVIF_WASCAPTUREFILE: AviFlags = AviFlags(0x0001_0000);
+pub const AVIF_COPYRIGHTED: AviFlags = AviFlags(0x0002_0000);
+
+impl AviFlags {
+    pub fn contains(&self, flags: AviFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl std::ops::BitOr for AviFlags {
+    type Output = AviFlags;
+
+    fn bitor(self, rhs: AviFlags) -> AviFlags {
+        AviFlags(self.0 | rhs.0)
+    }
+}
+
+/// `strh` flags, as seen on
+/// https://learn.microsoft.com/en-us/previous-versions/windows/desktop/api/avifmt/ns-avifmt-avistreamheader
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamFlags(pub u32);
+
+/// the stream should not be played by default
+pub const AVISF_DISABLED: StreamFlags = StreamFlags(0x0000_0001);
+/// the video stream has palette changes
+pub const AVISF_VIDEO_PALCHANGES: StreamFlags = StreamFlags(0x0001_0000);
+
+impl StreamFlags {
+    pub fn contains(&self, flags: StreamFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl std::ops::BitOr for StreamFlags {
+    type Output = StreamFlags;
+
+    fn bitor(self, rhs: StreamFlags) -> StreamFlags {
+        StreamFlags(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct MainAVIHeader {
-    microsec_per_frame: u32,
-    max_bytes_per_sec: u32,
-    padding_granularity: u32,
-    flags: u32,
-    total_frames: u32,
-    initial_frames: u32,
-    streams: u32,
-    suggested_buffer_size: u32,
-    width: u32,
-    height: u32,
+    pub(crate) microsec_per_frame: u32,
+    pub(crate) max_bytes_per_sec: u32,
+    pub(crate) padding_granularity: u32,
+    pub(crate) flags: AviFlags,
+    pub(crate) total_frames: u32,
+    pub(crate) initial_frames: u32,
+    pub(crate) streams: u32,
+    pub(crate) suggested_buffer_size: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl MainAVIHeader {
+    /// writes the `avih` chunk content, reserved fields included
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        for v in [
+            self.microsec_per_frame,
+            self.max_bytes_per_sec,
+            self.padding_granularity,
+            self.flags.0,
+            self.total_frames,
+            self.initial_frames,
+            self.streams,
+            self.suggested_buffer_size,
+            self.width,
+            self.height,
+        ] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        w.write_all(&[0; 16])
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+
+    /// header for `streams` streams of `width` by `height` frames, lasting
+    /// `microsec_per_frame` each
+    pub fn new(microsec_per_frame: u32, streams: u32, width: u32, height: u32) -> MainAVIHeader {
+        MainAVIHeader {
+            microsec_per_frame,
+            streams,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_bytes_per_sec(mut self, max_bytes_per_sec: u32) -> MainAVIHeader {
+        self.max_bytes_per_sec = max_bytes_per_sec;
+        self
+    }
+
+    pub fn with_padding_granularity(mut self, padding_granularity: u32) -> MainAVIHeader {
+        self.padding_granularity = padding_granularity;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: AviFlags) -> MainAVIHeader {
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_total_frames(mut self, total_frames: u32) -> MainAVIHeader {
+        self.total_frames = total_frames;
+        self
+    }
+
+    pub fn with_initial_frames(mut self, initial_frames: u32) -> MainAVIHeader {
+        self.initial_frames = initial_frames;
+        self
+    }
+
+    pub fn with_suggested_buffer_size(mut self, suggested_buffer_size: u32) -> MainAVIHeader {
+        self.suggested_buffer_size = suggested_buffer_size;
+        self
+    }
+
+    /// duration of a video frame, in microseconds
+    pub fn microsec_per_frame(&self) -> u32 {
+        self.microsec_per_frame
+    }
+
+    /// approximate data rate of the file, in bytes per second
+    pub fn max_bytes_per_sec(&self) -> u32 {
+        self.max_bytes_per_sec
+    }
+
+    /// the data is padded to multiples of this size, in bytes
+    pub fn padding_granularity(&self) -> u32 {
+        self.padding_granularity
+    }
+
+    pub fn flags(&self) -> AviFlags {
+        self.flags
+    }
+
+    /// number of video frames in the first `RIFF` chunk only, for OpenDML files
+    pub fn total_frames(&self) -> u32 {
+        self.total_frames
+    }
+
+    /// frames before the first one of interleaved files
+    pub fn initial_frames(&self) -> u32 {
+        self.initial_frames
+    }
+
+    pub fn streams(&self) -> u32 {
+        self.streams
+    }
+
+    /// buffer size needed to read a chunk, in bytes
+    pub fn suggested_buffer_size(&self) -> u32 {
+        self.suggested_buffer_size
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 }
 
 pub fn avih(input: &[u8]) -> IResult<&[u8], MainAVIHeader> {
@@ -142,7 +557,7 @@ pub fn avih(input: &[u8]) -> IResult<&[u8], MainAVIHeader> {
             microsec_per_frame: t.0,
             max_bytes_per_sec: t.1,
             padding_granularity: t.2,
-            flags: t.3,
+            flags: AviFlags(t.3),
             total_frames: t.4,
             initial_frames: t.5,
             streams: t.6,
@@ -153,30 +568,257 @@ pub fn avih(input: &[u8]) -> IResult<&[u8], MainAVIHeader> {
     )(input)
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// ODMLExtendedAVIHeader, the `dmlh` chunk of the `LIST odml` in OpenDML files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtendedAVIHeader {
+    /// number of video frames in the whole file, `MainAVIHeader::total_frames` only
+    /// counting the ones of the first `RIFF` chunk
+    pub grand_frames: u32,
+}
+
+/// size of the `dmlh` chunk content written by muxers, most of it being reserved
+const DMLH_SIZE: usize = 248;
+
+impl ExtendedAVIHeader {
+    /// writes the `dmlh` chunk content, reserved fields included
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.grand_frames.to_le_bytes())?;
+        w.write_all(&[0; DMLH_SIZE - 4])
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+}
+
+/// `dmlh` chunk content, the reserved fields are ignored
+pub fn dmlh(input: &[u8]) -> IResult<&[u8], ExtendedAVIHeader> {
+    map(terminated(le_u32, rest), |grand_frames| ExtendedAVIHeader {
+        grand_frames,
+    })(input)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Rect {
-    left: i16,
-    top: i16,
-    right: i16,
-    bottom: i16,
+    pub(crate) left: i16,
+    pub(crate) top: i16,
+    pub(crate) right: i16,
+    pub(crate) bottom: i16,
+}
+
+impl Rect {
+    pub fn new(left: i16, top: i16, right: i16, bottom: i16) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn left(&self) -> i16 {
+        self.left
+    }
+
+    pub fn top(&self) -> i16 {
+        self.top
+    }
+
+    pub fn right(&self) -> i16 {
+        self.right
+    }
+
+    pub fn bottom(&self) -> i16 {
+        self.bottom
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        for v in [self.left, self.top, self.right, self.bottom] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct AVIStreamHeader {
     pub fcc_type: FccType,
-    fcc_handler: u32,
-    flags: u32,
-    priority: u16,
-    language: u16,
-    initial_frames: u32,
-    scale: u32,
-    rate: u32,
-    start: u32,
-    length: u32,
-    suggested_buffer_size: u32,
-    quality: u32,
-    sample_size: u32,
-    frame: Rect,
+    pub(crate) fcc_handler: FourCC,
+    pub(crate) flags: StreamFlags,
+    pub(crate) priority: u16,
+    pub(crate) language: u16,
+    pub(crate) initial_frames: u32,
+    pub(crate) scale: u32,
+    pub(crate) rate: u32,
+    pub(crate) start: u32,
+    pub(crate) length: u32,
+    pub(crate) suggested_buffer_size: u32,
+    pub(crate) quality: u32,
+    pub(crate) sample_size: u32,
+    pub(crate) frame: Rect,
+}
+
+impl AVIStreamHeader {
+    /// writes the `strh` chunk content
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(self.fcc_type.fourcc().as_bytes())?;
+        w.write_all(self.fcc_handler.as_bytes())?;
+        w.write_all(&self.flags.0.to_le_bytes())?;
+        w.write_all(&self.priority.to_le_bytes())?;
+        w.write_all(&self.language.to_le_bytes())?;
+        for v in [
+            self.initial_frames,
+            self.scale,
+            self.rate,
+            self.start,
+            self.length,
+            self.suggested_buffer_size,
+            self.quality,
+            self.sample_size,
+        ] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        self.frame.write_to(w)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+
+    /// stream of `fcc_type` with a `scale / rate` seconds time unit
+    pub fn new(fcc_type: FccType, fcc_handler: FourCC, scale: u32, rate: u32) -> AVIStreamHeader {
+        AVIStreamHeader {
+            fcc_type,
+            fcc_handler,
+            flags: StreamFlags::default(),
+            priority: 0,
+            language: 0,
+            initial_frames: 0,
+            scale,
+            rate,
+            start: 0,
+            length: 0,
+            suggested_buffer_size: 0,
+            quality: u32::MAX,
+            sample_size: 0,
+            frame: Rect::default(),
+        }
+    }
+
+    pub fn with_flags(mut self, flags: StreamFlags) -> AVIStreamHeader {
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u16) -> AVIStreamHeader {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_language(mut self, language: u16) -> AVIStreamHeader {
+        self.language = language;
+        self
+    }
+
+    pub fn with_initial_frames(mut self, initial_frames: u32) -> AVIStreamHeader {
+        self.initial_frames = initial_frames;
+        self
+    }
+
+    pub fn with_start(mut self, start: u32) -> AVIStreamHeader {
+        self.start = start;
+        self
+    }
+
+    pub fn with_length(mut self, length: u32) -> AVIStreamHeader {
+        self.length = length;
+        self
+    }
+
+    pub fn with_suggested_buffer_size(mut self, suggested_buffer_size: u32) -> AVIStreamHeader {
+        self.suggested_buffer_size = suggested_buffer_size;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: u32) -> AVIStreamHeader {
+        self.quality = quality;
+        self
+    }
+
+    pub fn with_sample_size(mut self, sample_size: u32) -> AVIStreamHeader {
+        self.sample_size = sample_size;
+        self
+    }
+
+    pub fn with_frame(mut self, frame: Rect) -> AVIStreamHeader {
+        self.frame = frame;
+        self
+    }
+
+    /// codec fourcc, can be empty for audio streams
+    pub fn fcc_handler(&self) -> FourCC {
+        self.fcc_handler
+    }
+
+    pub fn flags(&self) -> StreamFlags {
+        self.flags
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    pub fn language(&self) -> u16 {
+        self.language
+    }
+
+    /// how far ahead of the video the stream is interleaved, in frames
+    pub fn initial_frames(&self) -> u32 {
+        self.initial_frames
+    }
+
+    /// time unit of the stream is `scale / rate` seconds
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// delay before the first sample, in stream time units
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// length of the stream, in `scale / rate` seconds units
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// buffer size needed to read a chunk, in bytes
+    pub fn suggested_buffer_size(&self) -> u32 {
+        self.suggested_buffer_size
+    }
+
+    /// between 0 and 10000, or -1 (`u32::MAX`) for the default quality
+    pub fn quality(&self) -> u32 {
+        self.quality
+    }
+
+    /// size of a sample in bytes, 0 if samples have a variable size
+    pub fn sample_size(&self) -> u32 {
+        self.sample_size
+    }
+
+    /// where the video or text is drawn in the `avih` sized frame
+    pub fn frame(&self) -> &Rect {
+        &self.frame
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -186,16 +828,26 @@ pub enum FccType {
     Subtitle,
 }
 
+impl FccType {
+    pub fn fourcc(&self) -> FourCC {
+        match self {
+            FccType::Video => FourCC::VIDS,
+            FccType::Audio => FourCC::AUDS,
+            FccType::Subtitle => FourCC::TXTS,
+        }
+    }
+}
+
 pub fn strh(input: &[u8]) -> IResult<&[u8], AVIStreamHeader> {
     map(
         tuple((
-            fcc_type, le_u32, le_u32, le_u16, le_u16, le_u32, le_u32, le_u32, le_u32, le_u32,
+            fcc_type, fourcc, le_u32, le_u16, le_u16, le_u32, le_u32, le_u32, le_u32, le_u32,
             le_u32, le_u32, le_u32, le_i16, le_i16, le_i16, le_i16,
         )),
         |t| AVIStreamHeader {
             fcc_type: t.0,
             fcc_handler: t.1,
-            flags: t.2,
+            flags: StreamFlags(t.2),
             priority: t.3,
             language: t.4,
             initial_frames: t.5,
@@ -216,46 +868,185 @@ pub fn strh(input: &[u8]) -> IResult<&[u8], AVIStreamHeader> {
     )(input)
 }
 
+/// `strn` chunk content, a null terminated string
+///
+/// the encoding is not specified, invalid UTF-8 sequences are replaced
+pub fn strn(input: &[u8]) -> IResult<&[u8], String> {
+    map(terminated(take_till(|b| b == 0), rest), |name| {
+        String::from_utf8_lossy(name).into_owned()
+    })(input)
+}
+
 pub fn fcc_type(input: &[u8]) -> IResult<&[u8], FccType> {
-    map(take(4usize), |val: &[u8]| match val {
-        b"vids" => FccType::Video,
-        b"auds" => FccType::Audio,
-        b"txts" => FccType::Subtitle,
-        _ => unreachable!(),
+    map_opt(take(4usize), |val: &[u8]| match val {
+        b"vids" => Some(FccType::Video),
+        b"auds" => Some(FccType::Audio),
+        b"txts" => Some(FccType::Subtitle),
+        _ => None,
     })(input)
 }
 
+/// video `strf` chunk, found after a `vids` stream header
+///
+/// the bytes following the 40 bytes structure, like the codec data of HuffYUV or FFV1,
+/// or the palette of 8 bits frames, are kept in `extra_data`
 pub fn strf(input: &[u8]) -> IResult<&[u8], BitmapInfoHeader> {
-    map(
-        tuple((
-            tag(b"strf"),
-            verify(le_u32, |val| *val == 40),
-            bitmap_info_header,
-        )),
-        |t| t.2,
-    )(input)
+    let (i, (_, size)) = tuple((tag(b"strf"), verify(le_u32, |val| *val >= 40)))(input)?;
+
+    map_parser(
+        terminated(take(size), take(size & 1)),
+        map(
+            tuple((bitmap_info_header, rest)),
+            |(mut bitmap, extra_data)| {
+                bitmap.extra_data = extra_data.to_vec();
+                bitmap
+            },
+        ),
+    )(i)
 }
 
+/// uncompressed frames
+pub const BI_RGB: FourCC = FourCC([0, 0, 0, 0]);
+/// uncompressed frames with color masks
+pub const BI_BITFIELDS: FourCC = FourCC([3, 0, 0, 0]);
+
 /// as seen on https://msdn.microsoft.com/en-us/library/windows/desktop/dd183376(v=vs.85).aspx
 #[derive(Debug, Clone, PartialEq)]
 pub struct BitmapInfoHeader {
-    size: u32,
-    width: i32,
-    height: i32,
-    planes: u16,
-    bit_count: u16,
-    compression: u32,
-    size_image: u32,
-    xpels_per_meter: i32,
-    ypels_per_meter: i32,
-    clr_used: u32,
-    clr_important: u32,
+    pub(crate) size: u32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    pub(crate) planes: u16,
+    pub(crate) bit_count: u16,
+    pub(crate) compression: FourCC,
+    pub(crate) size_image: u32,
+    pub(crate) xpels_per_meter: i32,
+    pub(crate) ypels_per_meter: i32,
+    pub(crate) clr_used: u32,
+    pub(crate) clr_important: u32,
+    pub(crate) extra_data: Vec<u8>,
+}
+
+impl BitmapInfoHeader {
+    /// writes the 40 bytes parsed by `bitmap_info_header()`, then the extra data
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.size.to_le_bytes())?;
+        w.write_all(&self.width.to_le_bytes())?;
+        w.write_all(&self.height.to_le_bytes())?;
+        w.write_all(&self.planes.to_le_bytes())?;
+        w.write_all(&self.bit_count.to_le_bytes())?;
+        w.write_all(self.compression.as_bytes())?;
+        w.write_all(&self.size_image.to_le_bytes())?;
+        w.write_all(&self.xpels_per_meter.to_le_bytes())?;
+        w.write_all(&self.ypels_per_meter.to_le_bytes())?;
+        w.write_all(&self.clr_used.to_le_bytes())?;
+        w.write_all(&self.clr_important.to_le_bytes())?;
+        w.write_all(&self.extra_data)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+
+    /// `width` by `height` frames of `bit_count` bits per pixel
+    pub fn new(width: i32, height: i32, bit_count: u16, compression: FourCC) -> BitmapInfoHeader {
+        BitmapInfoHeader {
+            size: 40,
+            width,
+            height,
+            planes: 1,
+            bit_count,
+            compression,
+            size_image: 0,
+            xpels_per_meter: 0,
+            ypels_per_meter: 0,
+            clr_used: 0,
+            clr_important: 0,
+            extra_data: Vec::new(),
+        }
+    }
+
+    pub fn with_size_image(mut self, size_image: u32) -> BitmapInfoHeader {
+        self.size_image = size_image;
+        self
+    }
+
+    pub fn with_pels_per_meter(mut self, x: i32, y: i32) -> BitmapInfoHeader {
+        self.xpels_per_meter = x;
+        self.ypels_per_meter = y;
+        self
+    }
+
+    pub fn with_colors(mut self, clr_used: u32, clr_important: u32) -> BitmapInfoHeader {
+        self.clr_used = clr_used;
+        self.clr_important = clr_important;
+        self
+    }
+
+    pub fn with_extra_data(mut self, extra_data: Vec<u8>) -> BitmapInfoHeader {
+        self.extra_data = extra_data;
+        self
+    }
+
+    /// size of the structure, in bytes
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// negative for uncompressed frames stored top down
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn planes(&self) -> u16 {
+        self.planes
+    }
+
+    pub fn bit_count(&self) -> u16 {
+        self.bit_count
+    }
+
+    /// codec fourcc, or `BI_RGB` (0) for uncompressed frames
+    pub fn compression(&self) -> FourCC {
+        self.compression
+    }
+
+    /// size of a frame in bytes, can be 0 for uncompressed frames
+    pub fn size_image(&self) -> u32 {
+        self.size_image
+    }
+
+    pub fn xpels_per_meter(&self) -> i32 {
+        self.xpels_per_meter
+    }
+
+    pub fn ypels_per_meter(&self) -> i32 {
+        self.ypels_per_meter
+    }
+
+    /// colors in the palette, 0 for the maximum allowed by `bit_count`
+    pub fn clr_used(&self) -> u32 {
+        self.clr_used
+    }
+
+    pub fn clr_important(&self) -> u32 {
+        self.clr_important
+    }
+
+    /// the bytes following the structure in the `strf` chunk
+    pub fn extra_data(&self) -> &[u8] {
+        &self.extra_data
+    }
 }
 
 pub fn bitmap_info_header(input: &[u8]) -> IResult<&[u8], BitmapInfoHeader> {
     map(
         tuple((
-            le_u32, le_i32, le_i32, le_u16, le_u16, le_u32, le_u32, le_i32, le_i32, le_u32, le_u32,
+            le_u32, le_i32, le_i32, le_u16, le_u16, fourcc, le_u32, le_i32, le_i32, le_u32, le_u32,
         )),
         |t| BitmapInfoHeader {
             size: t.0,
@@ -269,9 +1060,559 @@ pub fn bitmap_info_header(input: &[u8]) -> IResult<&[u8], BitmapInfoHeader> {
             ypels_per_meter: t.8,
             clr_used: t.9,
             clr_important: t.10,
+            extra_data: Vec::new(),
         },
     )(input)
 }
+
+/// VideoPropHeader, the `vprp` chunk found after the `strf` of video streams
+///
+/// as seen in the OpenDML AVI File Format Extensions, version 1.02
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoPropHeader {
+    pub video_format_token: VideoFormat,
+    pub video_standard: VideoStandard,
+    /// fields per second, like 50 or 60
+    pub vertical_refresh_rate: u32,
+    pub h_total_in_t: u32,
+    pub v_total_in_lines: u32,
+    /// `(x, y)`, the display aspect ratio of the frame like (4, 3) or (16, 9)
+    pub frame_aspect_ratio: (u16, u16),
+    pub frame_width_in_pixels: u32,
+    pub frame_height_in_lines: u32,
+    /// one for progressive frames, two for interlaced ones
+    pub field_info: Vec<VideoFieldDesc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    Unknown,
+    PalSquare,
+    PalCcir601,
+    NtscSquare,
+    NtscCcir601,
+    Other(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoStandard {
+    Unknown,
+    Pal,
+    Ntsc,
+    Secam,
+    Other(u32),
+}
+
+/// position of a field in the frame, and of its valid pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoFieldDesc {
+    pub compressed_bm_height: u32,
+    pub compressed_bm_width: u32,
+    pub valid_bm_height: u32,
+    pub valid_bm_width: u32,
+    pub valid_bm_x_offset: u32,
+    pub valid_bm_y_offset: u32,
+    pub video_x_offset_in_t: u32,
+    pub video_y_valid_start_line: u32,
+}
+
+impl VideoFormat {
+    fn from_u32(token: u32) -> VideoFormat {
+        match token {
+            0 => VideoFormat::Unknown,
+            1 => VideoFormat::PalSquare,
+            2 => VideoFormat::PalCcir601,
+            3 => VideoFormat::NtscSquare,
+            4 => VideoFormat::NtscCcir601,
+            t => VideoFormat::Other(t),
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            VideoFormat::Unknown => 0,
+            VideoFormat::PalSquare => 1,
+            VideoFormat::PalCcir601 => 2,
+            VideoFormat::NtscSquare => 3,
+            VideoFormat::NtscCcir601 => 4,
+            VideoFormat::Other(t) => t,
+        }
+    }
+}
+
+impl VideoStandard {
+    fn from_u32(standard: u32) -> VideoStandard {
+        match standard {
+            0 => VideoStandard::Unknown,
+            1 => VideoStandard::Pal,
+            2 => VideoStandard::Ntsc,
+            3 => VideoStandard::Secam,
+            s => VideoStandard::Other(s),
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            VideoStandard::Unknown => 0,
+            VideoStandard::Pal => 1,
+            VideoStandard::Ntsc => 2,
+            VideoStandard::Secam => 3,
+            VideoStandard::Other(s) => s,
+        }
+    }
+}
+
+impl VideoPropHeader {
+    /// writes the `vprp` chunk content
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let (x, y) = self.frame_aspect_ratio;
+        for v in [
+            self.video_format_token.to_u32(),
+            self.video_standard.to_u32(),
+            self.vertical_refresh_rate,
+            self.h_total_in_t,
+            self.v_total_in_lines,
+            (x as u32) << 16 | y as u32,
+            self.frame_width_in_pixels,
+            self.frame_height_in_lines,
+            self.field_info.len() as u32,
+        ] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        for f in &self.field_info {
+            for v in [
+                f.compressed_bm_height,
+                f.compressed_bm_width,
+                f.valid_bm_height,
+                f.valid_bm_width,
+                f.valid_bm_x_offset,
+                f.valid_bm_y_offset,
+                f.video_x_offset_in_t,
+                f.video_y_valid_start_line,
+            ] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+
+    /// the display aspect ratio, if it is set
+    pub fn aspect_ratio(&self) -> Option<(u32, u32)> {
+        match self.frame_aspect_ratio {
+            (0, _) | (_, 0) => None,
+            (x, y) => Some((x as u32, y as u32)),
+        }
+    }
+
+    pub fn interlaced(&self) -> bool {
+        self.field_info.len() == 2
+    }
+}
+
+pub fn vprp(input: &[u8]) -> IResult<&[u8], VideoPropHeader> {
+    map(
+        tuple((
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            length_count(le_u32, video_field_desc),
+        )),
+        |t| VideoPropHeader {
+            video_format_token: VideoFormat::from_u32(t.0),
+            video_standard: VideoStandard::from_u32(t.1),
+            vertical_refresh_rate: t.2,
+            h_total_in_t: t.3,
+            v_total_in_lines: t.4,
+            frame_aspect_ratio: ((t.5 >> 16) as u16, t.5 as u16),
+            frame_width_in_pixels: t.6,
+            frame_height_in_lines: t.7,
+            field_info: t.8,
+        },
+    )(input)
+}
+
+fn video_field_desc(input: &[u8]) -> IResult<&[u8], VideoFieldDesc> {
+    map(
+        tuple((
+            le_u32, le_u32, le_u32, le_u32, le_u32, le_u32, le_u32, le_u32,
+        )),
+        |t| VideoFieldDesc {
+            compressed_bm_height: t.0,
+            compressed_bm_width: t.1,
+            valid_bm_height: t.2,
+            valid_bm_width: t.3,
+            valid_bm_x_offset: t.4,
+            valid_bm_y_offset: t.5,
+            video_x_offset_in_t: t.6,
+            video_y_valid_start_line: t.7,
+        },
+    )(input)
+}
+
+/// audio `strf` chunk, found after an `auds` stream header
+pub fn strf_audio(input: &[u8]) -> IResult<&[u8], WaveFormatEx> {
+    let (i, (_, size)) = tuple((tag(b"strf"), le_u32))(input)?;
+
+    map_parser(terminated(take(size), take(size & 1)), wave_format_ex)(i)
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmeapi/ns-mmeapi-waveformatex
+///
+/// `extra_data` holds the `cb_size` bytes following the structure, if any, and
+/// `extension` their decoded form when the format tag is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveFormatEx {
+    pub format_tag: u16,
+    pub channels: u16,
+    pub samples_per_sec: u32,
+    pub avg_bytes_per_sec: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    pub cb_size: u16,
+    pub extra_data: Vec<u8>,
+    pub extension: Option<FormatExtension>,
+}
+
+impl WaveFormatEx {
+    /// writes the structure and its extra data, `cb_size` being their actual size
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.format_tag.to_le_bytes())?;
+        w.write_all(&self.channels.to_le_bytes())?;
+        w.write_all(&self.samples_per_sec.to_le_bytes())?;
+        w.write_all(&self.avg_bytes_per_sec.to_le_bytes())?;
+        w.write_all(&self.block_align.to_le_bytes())?;
+        w.write_all(&self.bits_per_sample.to_le_bytes())?;
+        w.write_all(&(self.extra_data.len() as u16).to_le_bytes())?;
+        w.write_all(&self.extra_data)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|w| self.write_to(w))
+    }
+}
+
+pub const WAVE_FORMAT_PCM: u16 = 0x0001;
+pub const WAVE_FORMAT_ADPCM: u16 = 0x0002;
+pub const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+pub const WAVE_FORMAT_IMA_ADPCM: u16 = 0x0011;
+pub const WAVE_FORMAT_MPEG: u16 = 0x0050;
+pub const WAVE_FORMAT_MPEGLAYER3: u16 = 0x0055;
+pub const WAVE_FORMAT_AAC: u16 = 0x00FF;
+pub const WAVE_FORMAT_MPEG_HEAAC: u16 = 0x1610;
+pub const WAVE_FORMAT_DOLBY_AC3: u16 = 0x2000;
+pub const WAVE_FORMAT_DTS: u16 = 0x2001;
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// decoded form of the bytes following a WAVEFORMATEX
+///
+/// formats without a known layout, like AC-3 or DTS, only have the raw `extra_data`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatExtension {
+    Extensible(WaveFormatExtensible),
+    MpegLayer3(MpegLayer3WaveFormat),
+    Mpeg1(Mpeg1WaveFormat),
+    Adpcm(AdpcmWaveFormat),
+    ImaAdpcm(ImaAdpcmWaveFormat),
+    Aac(AudioSpecificConfig),
+}
+
+fn format_extension(format_tag: u16, extra_data: &[u8]) -> Option<FormatExtension> {
+    let result = match format_tag {
+        WAVE_FORMAT_EXTENSIBLE => {
+            map(wave_format_extensible, FormatExtension::Extensible)(extra_data)
+        }
+        WAVE_FORMAT_MPEGLAYER3 => {
+            map(mpeg_layer3_wave_format, FormatExtension::MpegLayer3)(extra_data)
+        }
+        WAVE_FORMAT_MPEG => map(mpeg1_wave_format, FormatExtension::Mpeg1)(extra_data),
+        WAVE_FORMAT_ADPCM => map(adpcm_wave_format, FormatExtension::Adpcm)(extra_data),
+        WAVE_FORMAT_IMA_ADPCM => map(ima_adpcm_wave_format, FormatExtension::ImaAdpcm)(extra_data),
+        WAVE_FORMAT_AAC | WAVE_FORMAT_MPEG_HEAAC => {
+            map(audio_specific_config, FormatExtension::Aac)(extra_data)
+        }
+        _ => return None,
+    };
+
+    result.ok().map(|(_, e)| e)
+}
+
+/// parses a WAVEFORMATEX structure
+///
+/// the older 16 bytes PCMWAVEFORMAT (without `cb_size`) is accepted too, and a `cb_size`
+/// larger than the available data is truncated
+pub fn wave_format_ex(input: &[u8]) -> IResult<&[u8], WaveFormatEx> {
+    let (i, t) = tuple((le_u16, le_u16, le_u32, le_u32, le_u16, le_u16))(input)?;
+    let (i, cb_size) = opt(le_u16)(i)?;
+    let cb_size = cb_size.unwrap_or(0);
+    let (i, extra_data) = take(min(cb_size as usize, i.len()))(i)?;
+    let extension = format_extension(t.0, extra_data);
+
+    Ok((
+        i,
+        WaveFormatEx {
+            format_tag: t.0,
+            channels: t.1,
+            samples_per_sec: t.2,
+            avg_bytes_per_sec: t.3,
+            block_align: t.4,
+            bits_per_sample: t.5,
+            cb_size,
+            extra_data: extra_data.to_vec(),
+            extension,
+        },
+    ))
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-waveformatextensible
+///
+/// `valid_bits_per_sample` is the first member of the `Samples` union, which is used
+/// that way by every format we know of
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveFormatExtensible {
+    pub valid_bits_per_sample: u16,
+    pub channel_mask: ChannelMask,
+    pub sub_format: SubFormat,
+}
+
+/// the tail of a WAVEFORMATEXTENSIBLE, following `cb_size`
+pub fn wave_format_extensible(input: &[u8]) -> IResult<&[u8], WaveFormatExtensible> {
+    map(
+        tuple((le_u16, le_u32, map(take(16usize), sub_format))),
+        |t| WaveFormatExtensible {
+            valid_bits_per_sample: t.0,
+            channel_mask: ChannelMask(t.1),
+            sub_format: t.2,
+        },
+    )(input)
+}
+
+/// speaker positions present in the stream, in the `dwChannelMask` order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMask(pub u32);
+
+impl ChannelMask {
+    pub const FRONT_LEFT: u32 = 0x1;
+    pub const FRONT_RIGHT: u32 = 0x2;
+    pub const FRONT_CENTER: u32 = 0x4;
+    pub const LOW_FREQUENCY: u32 = 0x8;
+    pub const BACK_LEFT: u32 = 0x10;
+    pub const BACK_RIGHT: u32 = 0x20;
+    pub const FRONT_LEFT_OF_CENTER: u32 = 0x40;
+    pub const FRONT_RIGHT_OF_CENTER: u32 = 0x80;
+    pub const BACK_CENTER: u32 = 0x100;
+    pub const SIDE_LEFT: u32 = 0x200;
+    pub const SIDE_RIGHT: u32 = 0x400;
+    pub const TOP_CENTER: u32 = 0x800;
+    pub const TOP_FRONT_LEFT: u32 = 0x1000;
+    pub const TOP_FRONT_CENTER: u32 = 0x2000;
+    pub const TOP_FRONT_RIGHT: u32 = 0x4000;
+    pub const TOP_BACK_LEFT: u32 = 0x8000;
+    pub const TOP_BACK_CENTER: u32 = 0x10000;
+    pub const TOP_BACK_RIGHT: u32 = 0x20000;
+
+    pub fn contains(&self, speaker: u32) -> bool {
+        self.0 & speaker == speaker
+    }
+
+    /// number of speaker positions set in the mask
+    pub fn channels(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// the speaker positions, in the order their samples are interleaved
+    pub fn speakers(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..18)
+            .map(|shift| 1 << shift)
+            .filter(move |speaker| self.contains(*speaker))
+    }
+}
+
+/// the KSDATAFORMAT_SUBTYPE_* GUIDs from a WAVEFORMATEXTENSIBLE
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubFormat {
+    Pcm,
+    IeeeFloat,
+    Alaw,
+    Mulaw,
+    Adpcm,
+    Mpeg,
+    MpegLayer3,
+    Ac3,
+    Dts,
+    Aac,
+    /// a GUID derived from a WAVE format tag we don't list above
+    WaveFormat(u16),
+    Unknown([u8; 16]),
+}
+
+/// the last 12 bytes shared by all the GUIDs derived from a WAVE format tag,
+/// XXXXXXXX-0000-0010-8000-00aa00389b71
+const WAVE_FORMAT_GUID_BASE: [u8; 12] = [
+    0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+fn sub_format(guid: &[u8]) -> SubFormat {
+    if guid[4..] != WAVE_FORMAT_GUID_BASE || guid[2..4] != [0, 0] {
+        let mut raw = [0u8; 16];
+        raw.copy_from_slice(guid);
+        return SubFormat::Unknown(raw);
+    }
+
+    match u16::from_le_bytes([guid[0], guid[1]]) {
+        0x0001 => SubFormat::Pcm,
+        0x0003 => SubFormat::IeeeFloat,
+        0x0006 => SubFormat::Alaw,
+        0x0007 => SubFormat::Mulaw,
+        0x0002 => SubFormat::Adpcm,
+        0x0050 => SubFormat::Mpeg,
+        0x0055 => SubFormat::MpegLayer3,
+        0x0092 | 0x2000 => SubFormat::Ac3,
+        0x0008 | 0x2001 => SubFormat::Dts,
+        0x00ff | 0x1600 | 0x1610 | 0x706d => SubFormat::Aac,
+        tag => SubFormat::WaveFormat(tag),
+    }
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-mpeglayer3waveformat
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpegLayer3WaveFormat {
+    pub id: u16,
+    pub flags: u32,
+    pub block_size: u16,
+    pub frames_per_block: u16,
+    pub codec_delay: u16,
+}
+
+pub const MPEGLAYER3_ID_MPEG: u16 = 1;
+pub const MPEGLAYER3_FLAG_PADDING_ISO: u32 = 0;
+pub const MPEGLAYER3_FLAG_PADDING_ON: u32 = 1;
+pub const MPEGLAYER3_FLAG_PADDING_OFF: u32 = 2;
+
+pub fn mpeg_layer3_wave_format(input: &[u8]) -> IResult<&[u8], MpegLayer3WaveFormat> {
+    map(tuple((le_u16, le_u32, le_u16, le_u16, le_u16)), |t| {
+        MpegLayer3WaveFormat {
+            id: t.0,
+            flags: t.1,
+            block_size: t.2,
+            frames_per_block: t.3,
+            codec_delay: t.4,
+        }
+    })(input)
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-mpeg1waveformat
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mpeg1WaveFormat {
+    pub head_layer: u16,
+    pub head_bitrate: u32,
+    pub head_mode: u16,
+    pub head_mode_ext: u16,
+    pub head_emphasis: u16,
+    pub head_flags: u16,
+    pub pts: u64,
+}
+
+pub fn mpeg1_wave_format(input: &[u8]) -> IResult<&[u8], Mpeg1WaveFormat> {
+    map(
+        tuple((
+            le_u16, le_u32, le_u16, le_u16, le_u16, le_u16, le_u32, le_u32,
+        )),
+        |t| Mpeg1WaveFormat {
+            head_layer: t.0,
+            head_bitrate: t.1,
+            head_mode: t.2,
+            head_mode_ext: t.3,
+            head_emphasis: t.4,
+            head_flags: t.5,
+            pts: (t.7 as u64) << 32 | t.6 as u64,
+        },
+    )(input)
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-adpcmwaveformat
+///
+/// each coefficient pair is (`iCoef1`, `iCoef2`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdpcmWaveFormat {
+    pub samples_per_block: u16,
+    pub coefficients: Vec<(i16, i16)>,
+}
+
+pub fn adpcm_wave_format(input: &[u8]) -> IResult<&[u8], AdpcmWaveFormat> {
+    map(
+        tuple((le_u16, length_count(le_u16, tuple((le_i16, le_i16))))),
+        |t| AdpcmWaveFormat {
+            samples_per_block: t.0,
+            coefficients: t.1,
+        },
+    )(input)
+}
+
+/// as seen on https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-imaadpcmwaveformat
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImaAdpcmWaveFormat {
+    pub samples_per_block: u16,
+}
+
+pub fn ima_adpcm_wave_format(input: &[u8]) -> IResult<&[u8], ImaAdpcmWaveFormat> {
+    map(le_u16, |samples_per_block| ImaAdpcmWaveFormat {
+        samples_per_block,
+    })(input)
+}
+
+/// the first fields of an MPEG-4 AudioSpecificConfig (ISO/IEC 14496-3 1.6.2.1),
+/// which is what AAC streams carry as extra data
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSpecificConfig {
+    pub object_type: u8,
+    pub sampling_frequency: u32,
+    pub channel_configuration: u8,
+}
+
+const AAC_SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+pub fn audio_specific_config(input: &[u8]) -> IResult<&[u8], AudioSpecificConfig> {
+    bits::<_, _, Error<(&[u8], usize)>, _, _>(|i| {
+        let (i, object_type): (_, u8) = bits_take(5usize)(i)?;
+        let (i, object_type) = if object_type == 31 {
+            map(bits_take(6usize), |ext: u8| 32 + ext)(i)?
+        } else {
+            (i, object_type)
+        };
+        let (i, frequency_index): (_, usize) = bits_take(4usize)(i)?;
+        let (i, sampling_frequency) = if frequency_index == 15 {
+            bits_take(24usize)(i)?
+        } else {
+            let frequency = AAC_SAMPLING_FREQUENCIES
+                .get(frequency_index)
+                .copied()
+                .unwrap_or(0);
+            (i, frequency)
+        };
+        let (i, channel_configuration) = bits_take(4usize)(i)?;
+
+        Ok((
+            i,
+            AudioSpecificConfig {
+                object_type,
+                sampling_frequency,
+                channel_configuration,
+            },
+        ))
+    })(input)
+}
+
 #[cfg(test)]
 #[allow(non_upper_case_globals)]
 mod tests {
@@ -291,9 +1632,9 @@ mod tests {
             Ok((
                 &b""[..],
                 Header {
-                    magic1: b"RIFF",
+                    magic1: FourCC::RIFF,
                     file_size: 675628,
-                    magic2: b"AVI ",
+                    magic2: FourCC::AVI,
                 }
             ))
         );
@@ -305,9 +1646,9 @@ mod tests {
             Ok((
                 &b""[..],
                 Header {
-                    magic1: b"RIFF",
+                    magic1: FourCC::RIFF,
                     file_size: 1926660,
-                    magic2: b"AVI ",
+                    magic2: FourCC::AVI,
                 }
             ))
         );
@@ -323,7 +1664,7 @@ mod tests {
             Ok((
                 &b""[..],
                 BlockHeader {
-                    tag: b"LIST",
+                    tag: FourCC::LIST,
                     size: 192,
                 }
             ))
@@ -335,7 +1676,7 @@ mod tests {
             Ok((
                 &b""[..],
                 BlockHeader {
-                    tag: b"LIST",
+                    tag: FourCC::LIST,
                     size: 370,
                 }
             ))
@@ -345,10 +1686,10 @@ mod tests {
     #[test]
     fn parse_block() {
         println!("block:\n{}", &drop[12..24].to_hex(16));
-        let data = block(&drop[12..24], 12, 675628);
+        let data = block(&drop[12..24], 12, 675636);
         println!("data: {:?}", data);
         assert_eq!(data, Ok((&b""[..], Block::List(192, List::Hdrl))));
-        let data = block(&verona[12..24], 12, 1926660);
+        let data = block(&verona[12..24], 12, 1926668);
         println!("data: {:?}", data);
         assert_eq!(data, Ok((&b""[..], Block::List(370, List::Hdrl))));
     }
@@ -356,12 +1697,276 @@ mod tests {
     #[test]
     fn parse_block2() {
         println!("block:\n{}", &drop[112..120].to_hex(16));
-        let data = block(&drop[112..120], 112, 675628);
+        let data = block(&drop[112..120], 112, 675636);
         println!("data: {:?}", data);
         assert_eq!(data, Ok((&b""[..], Block::Default)));
         println!("block:\n{}", &verona[382..398].to_hex(16));
-        let data = block(&verona[382..390], 382, 1926660);
+        let data = block(&verona[382..390], 382, 1926668);
         println!("data: {:?}\n", data);
         assert_eq!(data, Ok((&b""[..], Block::Default)));
     }
+
+    #[test]
+    fn parse_strn_strd() {
+        let data = block(&verona[212..234], 212, 1926668);
+        assert_eq!(
+            data,
+            Ok((&b""[..], Block::Strn("Video stream".to_string())))
+        );
+
+        let strd = b"strd\x03\0\0\0abc\0";
+        let data = block(strd, 0, 12);
+        assert_eq!(data, Ok((&b""[..], Block::Strd(b"abc".to_vec()))));
+    }
+
+    #[test]
+    fn parse_vprp() {
+        // interlaced PAL, 16:9
+        let field = [288, 720, 288, 720, 0, 0, 0, 23];
+        let header = [2, 1, 50, 864, 625, 16 << 16 | 9, 720, 576, 2];
+        let values: Vec<u8> = [&header[..], &field, &field]
+            .concat()
+            .into_iter()
+            .flat_map(|v: u32| v.to_le_bytes())
+            .collect();
+
+        let chunk = [&b"vprp"[..], &(values.len() as u32).to_le_bytes(), &values].concat();
+        let (_, blk) = block(&chunk, 0, chunk.len()).unwrap();
+        let properties = match blk {
+            Block::Vprp(p) => p,
+            b => panic!("unexpected block {:?}", b),
+        };
+        assert_eq!(properties.video_format_token, VideoFormat::PalCcir601);
+        assert_eq!(properties.video_standard, VideoStandard::Pal);
+        assert_eq!(properties.aspect_ratio(), Some((16, 9)));
+        assert!(properties.interlaced());
+        assert_eq!(properties.field_info[1].video_y_valid_start_line, 23);
+        assert_eq!(properties.to_bytes(), values);
+    }
+
+    #[test]
+    fn parse_audio_strf() {
+        println!("block:\n{}", &verona[310..368].to_hex(16));
+        let (i, format) = strf_audio(&verona[310..368]).unwrap();
+        println!("data: {:?}", format);
+        assert!(i.is_empty());
+        assert_eq!(format.format_tag, 0x0002);
+        assert_eq!(format.channels, 1);
+        assert_eq!(format.samples_per_sec, 11025);
+        assert_eq!(format.avg_bytes_per_sec, 5644);
+        assert_eq!(format.block_align, 256);
+        assert_eq!(format.bits_per_sample, 4);
+        assert_eq!(format.cb_size, 32);
+        assert_eq!(format.extra_data.len(), 32);
+    }
+
+    #[test]
+    fn parse_pcm_wave_format() {
+        let data = [
+            0x01, 0x00, 0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x10, 0xb1, 0x02, 0x00, 0x04, 0x00,
+            0x10, 0x00,
+        ];
+        let (_, format) = wave_format_ex(&data).unwrap();
+        assert_eq!(format.format_tag, 1);
+        assert_eq!(format.samples_per_sec, 44100);
+        assert_eq!(format.cb_size, 0);
+        assert!(format.extra_data.is_empty());
+    }
+
+    #[test]
+    fn parse_wave_format_extensible() {
+        let data = [
+            0xfe, 0xff, 0x06, 0x00, 0x80, 0xbb, 0x00, 0x00, 0x00, 0x65, 0x04, 0x00, 0x18, 0x00,
+            0x20, 0x00, 0x16, 0x00, 0x18, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+        ];
+        let (i, format) = wave_format_ex(&data).unwrap();
+        println!("data: {:?}", format);
+        assert!(i.is_empty());
+        assert_eq!(format.format_tag, WAVE_FORMAT_EXTENSIBLE);
+        assert_eq!(format.cb_size, 22);
+
+        let extensible = match format.extension {
+            Some(FormatExtension::Extensible(e)) => e,
+            e => panic!("unexpected extension: {:?}", e),
+        };
+        assert_eq!(extensible.valid_bits_per_sample, 24);
+        assert_eq!(extensible.sub_format, SubFormat::IeeeFloat);
+        assert_eq!(extensible.channel_mask.channels(), 6);
+        assert!(extensible.channel_mask.contains(ChannelMask::LOW_FREQUENCY));
+        assert_eq!(
+            extensible.channel_mask.speakers().collect::<Vec<_>>(),
+            vec![
+                ChannelMask::FRONT_LEFT,
+                ChannelMask::FRONT_RIGHT,
+                ChannelMask::FRONT_CENTER,
+                ChannelMask::LOW_FREQUENCY,
+                ChannelMask::BACK_LEFT,
+                ChannelMask::BACK_RIGHT,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_adpcm_extension() {
+        let (_, format) = strf_audio(&verona[310..368]).unwrap();
+        assert_eq!(
+            format.extension,
+            Some(FormatExtension::Adpcm(AdpcmWaveFormat {
+                samples_per_block: 500,
+                coefficients: vec![
+                    (256, 0),
+                    (512, -256),
+                    (0, 0),
+                    (192, 64),
+                    (240, 0),
+                    (460, -208),
+                    (392, -232)
+                ],
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_mpeg_layer3_extension() {
+        let data = [
+            0x55, 0x00, 0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x40, 0x1f, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x0c, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0xa0, 0x01, 0x01, 0x00,
+            0x71, 0x05,
+        ];
+        let (_, format) = wave_format_ex(&data).unwrap();
+        assert_eq!(format.extra_data.len(), 12);
+        assert_eq!(
+            format.extension,
+            Some(FormatExtension::MpegLayer3(MpegLayer3WaveFormat {
+                id: MPEGLAYER3_ID_MPEG,
+                flags: MPEGLAYER3_FLAG_PADDING_OFF,
+                block_size: 416,
+                frames_per_block: 1,
+                codec_delay: 1393,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_aac_extension() {
+        let data = [
+            0xff, 0x00, 0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x00, 0x7d, 0x00, 0x00, 0x01, 0x00,
+            0x10, 0x00, 0x02, 0x00, 0x12, 0x10,
+        ];
+        let (_, format) = wave_format_ex(&data).unwrap();
+        assert_eq!(
+            format.extension,
+            Some(FormatExtension::Aac(AudioSpecificConfig {
+                object_type: 2,
+                sampling_frequency: 44100,
+                channel_configuration: 2,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_movi_data() {
+        let (_, chunks) = movi_data(&verona[2048..1913316], 2048).unwrap();
+        assert_eq!(chunks.len(), 834);
+
+        let (chunk, data) = &chunks[1];
+        assert_eq!(chunk.offset, 6790);
+        assert_eq!(chunk.stream, 1);
+        assert_eq!(chunk.kind, ChunkKind::Audio);
+        assert_eq!(data.len(), 5632);
+
+        let count = |kind| chunks.iter().filter(|(c, _)| c.kind == kind).count();
+        assert_eq!(count(ChunkKind::UncompressedVideo), 52);
+        assert_eq!(count(ChunkKind::CompressedVideo), 720);
+        assert_eq!(count(ChunkKind::Audio), 62);
+    }
+
+    #[test]
+    fn parse_movi_rec_lists() {
+        let mut data = b"LIST\x1c\0\0\0rec ".to_vec();
+        data.extend_from_slice(b"00dc\x03\0\0\0abc\0");
+        data.extend_from_slice(b"01wb\x02\0\0\0de");
+        data.extend_from_slice(b"JUNK\x02\0\0\0\0\0");
+        data.extend_from_slice(b"02tx\x01\0\0\0f\0");
+
+        let (_, chunks) = movi_data(&data, 100).unwrap();
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|(c, d)| (c.offset, c.stream, c.kind, *d))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (112, 0, ChunkKind::CompressedVideo, &b"abc"[..]),
+                (124, 1, ChunkKind::Audio, &b"de"[..]),
+                (144, 2, ChunkKind::Subtitle, &b"f"[..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn serialize_headers() {
+        for file in [drop, verona] {
+            let (_, h) = header(file).unwrap();
+            assert_eq!(h.to_bytes(), &file[..12]);
+            let (_, h) = block_header(&file[12..]).unwrap();
+            assert_eq!(h.to_bytes(), &file[12..20]);
+            let (_, h) = avih(&file[32..88]).unwrap();
+            assert_eq!(h.to_bytes(), &file[32..88]);
+        }
+
+        for (file, offset) in [(drop, 108), (verona, 108), (verona, 254)] {
+            let (_, h) = strh(&file[offset..offset + 56]).unwrap();
+            assert_eq!(h.to_bytes(), &file[offset..offset + 56]);
+            assert_eq!(h.frame.to_bytes(), &file[offset + 48..offset + 56]);
+        }
+
+        for file in [drop, verona] {
+            let (_, h) = bitmap_info_header(&file[172..212]).unwrap();
+            assert_eq!(h.to_bytes(), &file[172..212]);
+        }
+
+        let (_, f) = wave_format_ex(&verona[318..368]).unwrap();
+        assert_eq!(f.to_bytes(), &verona[318..368]);
+    }
+
+    #[test]
+    fn header_accessors() {
+        let (_, h) = avih(&verona[32..88]).unwrap();
+        assert_eq!(h.microsec_per_frame(), 80000);
+        assert_eq!((h.width(), h.height()), (280, 210));
+        assert_eq!(h.streams(), 2);
+        assert!(h.flags().contains(AVIF_HASINDEX | AVIF_TRUSTCKTYPE));
+        assert!(!h.flags().contains(AVIF_ISINTERLEAVED));
+        let built = MainAVIHeader::new(80000, 2, 280, 210)
+            .with_max_bytes_per_sec(45862)
+            .with_flags(AVIF_HASINDEX | AVIF_TRUSTCKTYPE)
+            .with_total_frames(385804)
+            .with_suggested_buffer_size(7905);
+        assert_eq!(built, h);
+
+        let (_, h) = strh(&verona[108..164]).unwrap();
+        assert_eq!(h.fcc_handler(), FourCC::new(b"MP42"));
+        assert_eq!((h.scale(), h.rate(), h.length()), (80000, 1000000, 772));
+        assert!(!h.flags().contains(AVISF_DISABLED));
+        assert_eq!(h.frame(), &Rect::new(0, 0, 280, 210));
+        let built = AVIStreamHeader::new(FccType::Video, h.fcc_handler(), 80000, 1000000)
+            .with_length(772)
+            .with_suggested_buffer_size(7905)
+            .with_quality(0)
+            .with_frame(Rect::new(0, 0, 280, 210));
+        assert_eq!(built, h);
+
+        let (_, h) = bitmap_info_header(&verona[172..212]).unwrap();
+        assert_eq!((h.width(), h.height(), h.bit_count()), (280, 210, 24));
+        assert_eq!(BitmapInfoHeader::new(280, 210, 24, h.compression()), h);
+
+        let (_, h) = header(&verona[..12]).unwrap();
+        assert_eq!(Header::new(1926660, FourCC::AVI), h);
+        assert_eq!((h.magic(), h.form_type()), (FourCC::RIFF, FourCC::AVI));
+        let (_, h) = block_header(&verona[12..20]).unwrap();
+        assert_eq!(h.tag(), FourCC::LIST);
+        assert_eq!(BlockHeader::new(FourCC::LIST, h.size()), h);
+    }
 }
@@ -0,0 +1,783 @@
+use std::cmp::{max, min};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::time::Duration;
+
+use crate::error::{AviError, AviErrorKind};
+use crate::fourcc::FourCC;
+use crate::index::{idx1, opendml_index, Index, IndexEntry, OpenDmlIndex};
+use crate::info::Metadata;
+use crate::keyframe::KeyframeHeuristic;
+use crate::parser::{ChunkKind, DataChunk, ExtendedAVIHeader, MainAVIHeader};
+use crate::state::{
+    advance, advance_with, Context, MoviList, SkipReason, SkippedChunk, State, StreamContext,
+};
+use crate::time::{StreamClock, Timestamp};
+
+/// how much data is read at once from the underlying reader
+const READ_SIZE: usize = 64 * 1024;
+
+/// how much of each chunk is read to guess keyframes when building the index
+const PROBE_SIZE: usize = 4096;
+
+/// a chunk of stream data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub stream: u16,
+    pub kind: ChunkKind,
+    pub data: Vec<u8>,
+    pub keyframe: bool,
+    /// presentation time of the first sample of the packet
+    pub pts: Timestamp,
+    /// absolute offset of the chunk in the file
+    pub offset: u64,
+}
+
+/// where reading resumes after a seek, in the stream the timestamp is given for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekMode {
+    /// the last keyframe at or before the timestamp, decoding can start there
+    PreviousKeyframe,
+    /// the chunk holding the timestamp, even if it is not a keyframe
+    Exact,
+    /// the keyframe closest to the timestamp, before or after it
+    Nearest,
+}
+
+/// demuxes an AVI file from any seekable source
+///
+/// the headers are parsed on creation, then `next_packet()` returns the stream data in
+/// file order, following the OpenDML `RIFF AVIX` extensions
+pub struct AviReader<R> {
+    reader: R,
+    state: State,
+    /// data read from `position` onwards, that the state machine did not consume yet
+    buffer: Vec<u8>,
+    position: usize,
+    eof: bool,
+    header: Context,
+    main_header: MainAVIHeader,
+    index: Option<Index>,
+    clocks: Vec<StreamClock>,
+    /// the `movi` lists found so far, the extension ones are parsed when seeking
+    movi_lists: Vec<MoviList>,
+    corrupted: Vec<SkippedChunk>,
+}
+
+impl<R: Read + Seek> AviReader<R> {
+    /// parses the file headers, up to the beginning of the stream data, and loads the index
+    pub fn new(reader: R) -> Result<AviReader<R>, AviError> {
+        let mut avi = AviReader {
+            reader,
+            state: State::Initial,
+            buffer: Vec::new(),
+            position: 0,
+            eof: false,
+            header: Context::default(),
+            main_header: MainAVIHeader::default(),
+            index: None,
+            clocks: Vec::new(),
+            movi_lists: Vec::new(),
+            corrupted: Vec::new(),
+        };
+
+        avi.header = avi.read_headers()?;
+        avi.movi_lists = avi.header.movi_lists().to_vec();
+        avi.main_header = match avi.header.main_header() {
+            Some(h) => h.clone(),
+            None => return Err(avi.missing(FourCC::AVIH)),
+        };
+        avi.clocks = avi.header.streams().iter().map(StreamClock::new).collect();
+        avi.index = avi.load_index();
+
+        Ok(avi)
+    }
+
+    pub fn main_header(&self) -> &MainAVIHeader {
+        &self.main_header
+    }
+
+    /// the OpenDML `dmlh` header, if the file has one
+    pub fn extended_header(&self) -> Option<&ExtendedAVIHeader> {
+        self.header.extended_header()
+    }
+
+    /// number of video frames in the file, from the `dmlh` header of OpenDML files since
+    /// the main header one only covers the first `RIFF` chunk
+    pub fn total_frames(&self) -> u32 {
+        match self.extended_header() {
+            Some(h) => h.grand_frames,
+            None => self.main_header.total_frames(),
+        }
+    }
+
+    /// duration of the file, from the number of video frames and their duration
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(
+            self.total_frames() as u64 * self.main_header.microsec_per_frame() as u64,
+        )
+    }
+
+    /// the `LIST INFO` entries found in the headers
+    pub fn metadata(&self) -> &Metadata {
+        self.header.metadata()
+    }
+
+    /// the stream descriptions, the position in the list being the stream number
+    pub fn streams(&self) -> &[StreamContext] {
+        self.header.streams()
+    }
+
+    /// the OpenDML index if the file has one, the legacy `idx1` otherwise
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
+    }
+
+    /// in recovery mode, the stream data following a corrupted chunk header is skipped up
+    /// to the next chunk instead of failing, see `corrupted()`
+    pub fn set_recover(&mut self, recover: bool) {
+        self.header.set_recover(recover);
+        if let Some(ctx) = self.state.context_mut() {
+            ctx.set_recover(recover);
+        }
+    }
+
+    /// the ranges skipped in recovery mode so far, starting at a corrupted chunk header
+    pub fn corrupted(&self) -> &[SkippedChunk] {
+        &self.corrupted
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// returns the next packet, or `None` at the end of the file
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, AviError> {
+        let (chunk, advancing) = match self.next_chunk()? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let data = self.read_at(chunk.offset + 8, chunk.size as usize)?;
+        self.consume(advancing);
+
+        Ok(Some(self.packet(&chunk, data)))
+    }
+
+    /// builds the index by reading every data chunk of the file, for files without one
+    ///
+    /// there are no keyframe flags then, they are guessed from the chunk data depending on
+    /// the codec. The reading position is kept.
+    pub fn build_index(&mut self) -> Result<&Index, AviError> {
+        let heuristics: Vec<_> = self
+            .header
+            .streams()
+            .iter()
+            .map(KeyframeHeuristic::new)
+            .collect();
+        let movi = self.movi_lists[0].clone();
+        let state = mem::replace(
+            &mut self.state,
+            State::Blocks(self.header.seek_movi(&movi, movi.offset + 4)),
+        );
+        let position = self.position;
+        self.reposition(movi.offset + 4);
+
+        let index = self.scan_chunks(&heuristics);
+        if let State::End(ctx) | State::Blocks(ctx) = &self.state {
+            if ctx.movi_lists().len() > self.movi_lists.len() {
+                self.movi_lists = ctx.movi_lists().to_vec();
+            }
+        }
+        self.state = state;
+        self.reposition(position);
+
+        Ok(self.index.insert(index?))
+    }
+
+    fn scan_chunks(&mut self, heuristics: &[KeyframeHeuristic]) -> Result<Index, AviError> {
+        let mut index = Index::default();
+        while let Some((chunk, advancing)) = self.next_chunk()? {
+            let probe = self.read_at(chunk.offset + 8, min(chunk.size as usize, PROBE_SIZE))?;
+            self.consume(advancing);
+
+            let heuristic = heuristics
+                .get(chunk.stream as usize)
+                .unwrap_or(&KeyframeHeuristic::First);
+            let entries = &mut index.stream_mut(chunk.stream).entries;
+            entries.push(IndexEntry {
+                offset: chunk.offset as u64,
+                size: chunk.size,
+                keyframe: heuristic.is_keyframe(chunk.kind, &probe, entries.is_empty()),
+                kind: chunk.kind,
+            });
+        }
+
+        Ok(index)
+    }
+
+    /// moves to `time` in `stream`, the next packets of every stream being read from there
+    ///
+    /// the index is built first for files without one. Returns the timestamp of the packet `stream` resumes at, the
+    /// other streams resume at their first packet after it in the file.
+    pub fn seek(
+        &mut self,
+        stream: u16,
+        time: Duration,
+        mode: SeekMode,
+    ) -> Result<Timestamp, AviError> {
+        let mut clock = match self.clocks.get(stream as usize) {
+            Some(c) => c.clone(),
+            None => {
+                return Err(AviError::new(
+                    self.position,
+                    None,
+                    AviErrorKind::UnknownStream(stream),
+                ))
+            }
+        };
+        if self.index.is_none() {
+            self.build_index()?;
+        }
+        let index = match self.index.as_ref().and_then(|i| i.stream(stream)) {
+            Some(s) if !s.is_empty() => s,
+            _ => return Err(self.missing(FourCC::IDX1)),
+        };
+
+        let ticks = clock.chunk_ticks(index.entries.iter().map(|e| (e.kind, e.size)));
+        clock.set_ticks(0);
+        let start = clock.timestamp();
+        let target = Timestamp::from_duration(time, start.scale, start.rate)
+            .value
+            .saturating_sub(start.value);
+        let exact = ticks[..index.len()]
+            .partition_point(|t| *t <= target)
+            .saturating_sub(1);
+        let position = match (
+            mode,
+            index.keyframe_before(exact),
+            index.keyframe_after(exact),
+        ) {
+            (SeekMode::Exact, _, _) => exact,
+            (SeekMode::Nearest, Some(b), Some(a))
+                if ticks[a].abs_diff(target) < ticks[b].abs_diff(target) =>
+            {
+                a
+            }
+            (_, Some(b), _) => b,
+            (_, None, Some(a)) => a,
+            (_, None, None) => exact,
+        };
+        clock.set_ticks(ticks[position]);
+        let offset = index.entries[position].offset;
+
+        let movi = self.movi_list(offset as usize)?;
+        self.state = State::Blocks(self.header.seek_movi(&movi, offset as usize));
+        self.reposition(offset as usize);
+
+        let index = self.index.as_ref().map_or(&[][..], |i| &i.streams[..]);
+        for (clock, entries) in self.clocks.iter_mut().zip(index) {
+            let ticks = clock.chunk_ticks(entries.entries.iter().map(|e| (e.kind, e.size)));
+            let next = entries.entries.partition_point(|e| e.offset < offset);
+            clock.set_ticks(ticks[next]);
+        }
+
+        Ok(clock.timestamp())
+    }
+
+    /// runs the state machine up to the next data chunk, the returned advancing count
+    /// covering it
+    fn next_chunk(&mut self) -> Result<Option<(DataChunk, usize)>, AviError> {
+        loop {
+            let state = mem::replace(&mut self.state, State::Initial);
+            let corrupted = &mut self.corrupted;
+            let (advancing, state) = advance_with(state, &self.buffer, &mut |c| {
+                if c.reason == SkipReason::Corrupted {
+                    corrupted.push(c.clone());
+                }
+            })?;
+
+            match state {
+                State::End(ctx) => {
+                    self.state = State::End(ctx);
+                    return Ok(None);
+                }
+                state if advancing == 0 => {
+                    self.state = state;
+                    if !self.fill()? {
+                        return Ok(None);
+                    }
+                }
+                State::Packet(ctx, chunk) => {
+                    self.state = State::Blocks(ctx);
+                    return Ok(Some((chunk, advancing)));
+                }
+                state => {
+                    self.state = state;
+                    self.consume(advancing);
+                }
+            }
+        }
+    }
+
+    /// runs the state machine up to the beginning of the next `movi` list
+    fn read_headers(&mut self) -> Result<Context, AviError> {
+        loop {
+            let state = mem::replace(&mut self.state, State::Initial);
+            let (advancing, state) = advance(state, &self.buffer)?;
+
+            match state {
+                State::Blocks(ctx) if ctx.in_movi() => {
+                    self.state = State::Blocks(ctx.clone());
+                    self.consume(advancing);
+                    return Ok(ctx);
+                }
+                State::End(_) => return Err(self.missing(FourCC::MOVI)),
+                state => {
+                    self.state = state;
+                    if advancing > 0 {
+                        self.consume(advancing);
+                    } else if !self.fill()? {
+                        return Err(self.missing(FourCC::MOVI));
+                    }
+                }
+            }
+        }
+    }
+
+    /// the `movi` list holding the chunk at `offset`, parsing the following `RIFF AVIX`
+    /// headers if it was not found yet
+    fn movi_list(&mut self, offset: usize) -> Result<MoviList, AviError> {
+        if let Some(movi) = self.movi_lists.iter().find(|m| m.contains(offset)) {
+            return Ok(movi.clone());
+        }
+
+        let state = mem::replace(&mut self.state, State::Initial);
+        let position = self.position;
+        let movi = self.extension_movi_list(offset);
+        self.state = state;
+        self.reposition(position);
+
+        movi
+    }
+
+    fn extension_movi_list(&mut self, offset: usize) -> Result<MoviList, AviError> {
+        loop {
+            let last = match self.movi_lists.last() {
+                Some(last) if last.end_offset <= offset => last.clone(),
+                _ => return Err(AviError::new(offset, None, AviErrorKind::UnexpectedChunk)),
+            };
+            self.state = State::Blocks(self.header.seek_next_riff(&last));
+            self.reposition(last.riff_end);
+
+            let ctx = self.read_headers()?;
+            let movi = ctx.movi_lists().last().cloned();
+            self.movi_lists.extend(movi);
+
+            if let Some(movi) = self.movi_lists.iter().find(|m| m.contains(offset)) {
+                return Ok(movi.clone());
+            }
+        }
+    }
+
+    fn packet(&mut self, chunk: &DataChunk, data: Vec<u8>) -> Packet {
+        let stream = chunk.stream as usize;
+        if self.clocks.len() <= stream {
+            self.clocks.resize(stream + 1, StreamClock::default());
+        }
+        let clock = &mut self.clocks[stream];
+        // a palette change applies to the next frame, it does not take time
+        let pts = match chunk.kind {
+            ChunkKind::PaletteChange => clock.timestamp(),
+            _ => clock.advance(chunk.size),
+        };
+
+        Packet {
+            stream: chunk.stream,
+            kind: chunk.kind,
+            data,
+            keyframe: self.keyframe(chunk),
+            pts,
+            offset: chunk.offset as u64,
+        }
+    }
+
+    /// keyframe flag from the index, without one only video chunks can be delta frames
+    fn keyframe(&self, chunk: &DataChunk) -> bool {
+        let indexed = self
+            .index
+            .as_ref()
+            .and_then(|i| i.stream(chunk.stream))
+            .and_then(|s| {
+                s.entries
+                    .binary_search_by_key(&(chunk.offset as u64), |e| e.offset)
+                    .ok()
+                    .map(|p| s.entries[p].keyframe)
+            });
+
+        indexed.unwrap_or_else(|| {
+            !matches!(
+                self.header.streams().get(chunk.stream as usize),
+                Some(StreamContext::Video(_))
+            )
+        })
+    }
+
+    /// loads the index chunks, which are located after the data we're about to read
+    ///
+    /// a broken index is ignored, the file can still be read without it
+    fn load_index(&mut self) -> Option<Index> {
+        let super_indexes = self.header.super_indexes().to_vec();
+        if !super_indexes.is_empty() {
+            let mut index = Index::default();
+            for entry in super_indexes.iter().flat_map(|s| &s.entries) {
+                let data = self.read_chunk(entry.offset as usize).ok()?;
+                if let Ok((_, OpenDmlIndex::Standard(s))) = opendml_index(&data) {
+                    index.add_standard_index(&s);
+                }
+            }
+
+            return Some(index);
+        }
+
+        let movi = self.header.movi_lists().first()?.clone();
+        let data = self.read_chunk(movi.end_offset).ok()?;
+        let (_, entries) = idx1(&data).ok()?;
+
+        Some(Index::from_idx1(&entries, movi.offset as u64))
+    }
+
+    /// reads a whole chunk, header included
+    fn read_chunk(&mut self, offset: usize) -> Result<Vec<u8>, AviError> {
+        let header = self.read_at(offset, 8)?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        self.read_at(offset, 8 + size as usize)
+    }
+
+    /// reads data anywhere in the file, from the buffer if possible
+    ///
+    /// `len` usually comes from a chunk size, so the data is read before allocating for
+    /// it: a corrupted size makes the read fail at the end of the file
+    fn read_at(&mut self, offset: usize, len: usize) -> Result<Vec<u8>, AviError> {
+        if offset >= self.position
+            && offset.saturating_add(len) <= self.position + self.buffer.len()
+        {
+            let start = offset - self.position;
+            return Ok(self.buffer[start..start + len].to_vec());
+        }
+
+        let mut data = Vec::new();
+        self.reader
+            .seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| (&mut self.reader).take(len as u64).read_to_end(&mut data))
+            .map_err(|e| AviError::io(offset, e))?;
+
+        if data.len() < len {
+            return Err(AviError::new(
+                offset,
+                None,
+                AviErrorKind::Io(io::ErrorKind::UnexpectedEof),
+            ));
+        }
+        Ok(data)
+    }
+
+    /// reads more data after the buffer, returns false at the end of the file
+    fn fill(&mut self) -> Result<bool, AviError> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        let len = self.buffer.len();
+        let offset = self.position + len;
+        let wanted = max(READ_SIZE, len);
+        let read = self
+            .reader
+            .seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| {
+                (&mut self.reader)
+                    .take(wanted as u64)
+                    .read_to_end(&mut self.buffer)
+            })
+            .map_err(|e| AviError::io(offset, e))?;
+
+        self.eof = read < wanted;
+        Ok(read > 0)
+    }
+
+    /// moves the current position forward, `advancing` can go past the buffered data
+    fn consume(&mut self, advancing: usize) {
+        if advancing <= self.buffer.len() {
+            self.buffer.drain(..advancing);
+        } else {
+            self.buffer.clear();
+            self.eof = false;
+        }
+        self.position += advancing;
+    }
+
+    /// drops the buffered data, the next read starting at `offset`
+    fn reposition(&mut self, offset: usize) {
+        self.buffer.clear();
+        self.position = offset;
+        self.eof = false;
+    }
+
+    fn missing(&self, tag: FourCC) -> AviError {
+        AviError::new(self.position, None, AviErrorKind::MissingChunk(tag))
+    }
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use std::io::{self, Cursor};
+    use std::time::Duration;
+
+    use super::*;
+
+    const drop: &[u8] = include_bytes!("../assets/drop.avi");
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    /// returns the data a few bytes at a time
+    struct SlowReader<'a>(Cursor<&'a [u8]>);
+
+    impl Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl Seek for SlowReader<'_> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    fn read_all<R: Read + Seek>(reader: &mut AviReader<R>) -> Vec<Packet> {
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[test]
+    fn read_drop() {
+        let mut reader = AviReader::new(Cursor::new(drop)).unwrap();
+        assert!(matches!(reader.streams(), [StreamContext::Video(_)]));
+        assert_eq!(reader.index().unwrap().stream(0).unwrap().len(), 182);
+
+        let packets = read_all(&mut reader);
+        assert_eq!(packets.len(), 182);
+        assert_eq!(packets[0].offset, 2048);
+        assert_eq!(packets[0].data, &drop[2056..2056 + 2686]);
+        assert!(packets[0].keyframe);
+        assert_eq!(packets[181].pts, Timestamp::new(181, 1, 30));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_verona() {
+        let mut reader = AviReader::new(SlowReader(Cursor::new(verona))).unwrap();
+        assert!(matches!(
+            reader.streams(),
+            [StreamContext::Video(_), StreamContext::Audio(_)]
+        ));
+
+        let packets = read_all(&mut reader);
+        let index = reader.index().unwrap();
+        assert_eq!(packets.len(), 834);
+
+        let last = |stream| packets.iter().rfind(|p| p.stream == stream).unwrap();
+        assert_eq!(last(0).pts.duration(), Duration::from_millis(771 * 80));
+        let audio_bytes: usize = packets
+            .iter()
+            .filter(|p| p.stream == 1)
+            .map(|p| p.data.len())
+            .sum();
+        let audio_end = last(1).pts.value + last(1).data.len() as u64 / 256;
+        assert_eq!(audio_end, audio_bytes as u64 / 256);
+        for stream in 0..2 {
+            let entries = &index.stream(stream).unwrap().entries;
+            let stream_packets: Vec<_> = packets.iter().filter(|p| p.stream == stream).collect();
+            assert_eq!(stream_packets.len(), entries.len());
+
+            for (packet, entry) in stream_packets.iter().zip(entries) {
+                assert_eq!(packet.offset, entry.offset);
+                assert_eq!(packet.data.len(), entry.size as usize);
+                assert_eq!(packet.keyframe, entry.keyframe);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_movi() {
+        let mut data = b"RIFF\x0c\0\0\0AVI LIST\x04\0\0\0hdrl".to_vec();
+        data.extend_from_slice(b"trailing");
+        let e = AviReader::new(Cursor::new(data)).err().unwrap();
+        assert_eq!(e.kind, AviErrorKind::MissingChunk(FourCC::MOVI));
+    }
+
+    #[test]
+    fn oversized_chunks() {
+        // an `idx1` size past the end of the file, the index is ignored
+        let mut data = drop.to_vec();
+        data[672720..672724].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert!(reader.index().is_none());
+        assert_eq!(reader.next_packet().unwrap().unwrap().offset, 2048);
+
+        let e = reader.read_chunk(672716).unwrap_err();
+        assert_eq!(e.offset, 672716);
+        assert_eq!(e.kind, AviErrorKind::Io(io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn palette_changes() {
+        // the second chunk of drop.avi as a palette change, in the data and the index
+        let mut data = drop.to_vec();
+        data[4744..4746].copy_from_slice(b"pc");
+        data[672742..672744].copy_from_slice(b"pc");
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        let all = read_all(&mut reader);
+
+        assert_eq!(all[1].kind, ChunkKind::PaletteChange);
+        assert_eq!(all[1].pts, Timestamp::new(1, 1, 30));
+        assert_eq!(all[2].pts, Timestamp::new(1, 1, 30));
+        assert_eq!(all[181].pts, Timestamp::new(180, 1, 30));
+
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        let t = reader
+            .seek(0, Duration::from_secs(3), SeekMode::Exact)
+            .unwrap();
+        assert_eq!(t, Timestamp::new(90, 1, 30));
+        let p = reader.next_packet().unwrap().unwrap();
+        assert_eq!((p.offset, p.pts), (all[91].offset, t));
+    }
+
+    /// packets read after seeking must be the same as when reading the whole file
+    fn check_seek(all: &[Packet], reader: &mut AviReader<Cursor<&[u8]>>) -> usize {
+        let rest = read_all(reader);
+        let start = all.iter().position(|p| p.offset == rest[0].offset).unwrap();
+        assert_eq!(rest, &all[start..]);
+        start
+    }
+
+    #[test]
+    fn seek_verona() {
+        let all = read_all(&mut AviReader::new(Cursor::new(verona)).unwrap());
+        let mut reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let time = Duration::from_secs(10);
+
+        let t = reader.seek(0, time, SeekMode::Exact).unwrap();
+        assert_eq!(t, Timestamp::new(125, 80000, 1000000));
+        let start = check_seek(&all, &mut reader);
+        assert_eq!(all[start].pts, t);
+        assert!(!all[start].keyframe);
+
+        let t = reader.seek(0, time, SeekMode::PreviousKeyframe).unwrap();
+        assert!(t.duration() <= time);
+        let start = check_seek(&all, &mut reader);
+        assert!(all[start].keyframe);
+        let next_keyframe = all[start + 1..]
+            .iter()
+            .find(|p| p.stream == 0 && p.keyframe)
+            .unwrap();
+        assert!(next_keyframe.pts.duration() > time);
+
+        let t = reader.seek(0, time, SeekMode::Nearest).unwrap();
+        let distance = |t: Timestamp| t.duration().abs_diff(time);
+        assert!(distance(t) <= distance(next_keyframe.pts));
+        check_seek(&all, &mut reader);
+
+        // audio streams can be seeked too, the video resuming at the next chunk
+        let t = reader.seek(1, time, SeekMode::Exact).unwrap();
+        assert!(t.duration() <= time && time < t.duration() + Duration::from_secs(1));
+        check_seek(&all, &mut reader);
+    }
+
+    #[test]
+    fn seek_errors() {
+        let mut reader = AviReader::new(Cursor::new(drop)).unwrap();
+        let e = reader.seek(1, Duration::ZERO, SeekMode::Exact).unwrap_err();
+        assert_eq!(e.kind, AviErrorKind::UnknownStream(1));
+
+        // the reader is still usable
+        assert_eq!(read_all(&mut reader).len(), 182);
+    }
+
+    #[test]
+    fn build_index() {
+        // without the idx1 chunk
+        let mut reader = AviReader::new(Cursor::new(&drop[..672716])).unwrap();
+        assert!(reader.index().is_none());
+        let packet = reader.next_packet().unwrap().unwrap();
+
+        let index = reader.build_index().unwrap().clone();
+        let expected = AviReader::new(Cursor::new(drop)).unwrap().index;
+        assert_eq!(Some(index), expected);
+        assert_eq!(read_all(&mut reader).len(), 181);
+
+        reader
+            .seek(0, Duration::from_secs(3), SeekMode::Exact)
+            .unwrap();
+        assert_eq!(reader.next_packet().unwrap().unwrap().pts.value, 90);
+        assert_eq!(packet.pts.value, 0);
+
+        // the keyframe flags of the MS MPEG-4 frames are guessed right
+        let mut reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let expected = reader.index.clone();
+        reader.index = None;
+        reader
+            .seek(0, Duration::from_secs(10), SeekMode::PreviousKeyframe)
+            .unwrap();
+        assert_eq!(reader.index, expected);
+    }
+
+    #[test]
+    fn find_extension_movi_lists() {
+        let chunk = |tag: &[u8], content: &[u8]| {
+            [tag, &(content.len() as u32).to_le_bytes(), content].concat()
+        };
+        let movi = chunk(b"LIST", &[&b"movi"[..], &chunk(b"00dc", b"ab")].concat());
+        let hdrl = chunk(b"LIST", &[&b"hdrl"[..], &chunk(b"avih", &[0; 56])].concat());
+        let mut data = chunk(b"RIFF", &[&b"AVI "[..], &hdrl, &movi].concat());
+        data.extend(chunk(b"RIFF", &[&b"AVIX"[..], &movi].concat()));
+        data.extend(chunk(b"RIFF", &[&b"AVIX"[..], &movi].concat()));
+
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        let movi = reader.movi_list(data.len() - 10).unwrap();
+        assert_eq!(movi.offset, data.len() - 14);
+        assert_eq!(movi.riff_end, data.len());
+        assert_eq!(reader.movi_lists.len(), 3);
+        assert!(reader.movi_list(data.len()).is_err());
+
+        // seeking in the extensions is done from the state they were parsed in
+        let movi = reader.movi_lists[1].clone();
+        reader.state = State::Blocks(reader.header.seek_movi(&movi, movi.offset + 4));
+        reader.reposition(movi.offset + 4);
+        let packets = read_all(&mut reader);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].data, b"ab");
+        assert_eq!(packets[1].offset as usize, data.len() - 10);
+    }
+
+    #[test]
+    fn recover_from_corrupted_chunks() {
+        let mut data = verona.to_vec();
+        data[2052..2056].copy_from_slice(&0xfffff0u32.to_le_bytes());
+
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert!(reader.next_packet().is_err());
+
+        let mut reader = AviReader::new(Cursor::new(&data[..])).unwrap();
+        reader.set_recover(true);
+        let packets = read_all(&mut reader);
+        assert_eq!(packets.len(), 833);
+        assert_eq!(packets[0].offset, 6790);
+        assert_eq!(reader.corrupted().len(), 1);
+        assert_eq!(reader.corrupted()[0].offset, 2048);
+    }
+}
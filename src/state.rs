@@ -1,29 +1,61 @@
 use std::cmp::{min, Ordering};
 
-use nom::{
-    bytes::complete::tag, error::Error, number::complete::le_u32, sequence::tuple, Err, HexDisplay,
-    Offset,
-};
+use nom::{Err, Offset};
 
-use crate::parser::{self, block, header, strf, AVIStreamHeader, BitmapInfoHeader, Block, FccType};
+use crate::codec::CodecId;
+use crate::error::{AviError, AviErrorKind};
+use crate::fourcc::FourCC;
+use crate::index::{Index, OpenDmlIndex, SuperIndex};
+use crate::info::Metadata;
+use crate::parser::{
+    self, block, chunk_kind, data_chunk, extension_header, fcc_type, header, stream_number, strf,
+    strf_audio, AVIStreamHeader, BitmapInfoHeader, Block, ChunkKind, DataChunk, ExtendedAVIHeader,
+    FccType, MainAVIHeader, VideoPropHeader, WaveFormatEx,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum State {
     Initial,
-    Error,
     Blocks(Context),
     VideoIndexStream(Context, VideoIndexState),
-    AudioIndexStream(Context),
-    SubtitleIndexStream(Context),
+    AudioIndexStream(Context, AVIStreamHeader),
+    SubtitleIndexStream(Context, AVIStreamHeader),
+    /// a data chunk was found in a `movi` list, the advancing count covers it entirely
+    Packet(Context, DataChunk),
+    /// the last `RIFF` chunk was parsed, anything after it is ignored
+    End(Context),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum VideoIndexState {
     Initial(AVIStreamHeader),
-    BMP(AVIStreamHeader, BitmapInfoHeader),
-    Index(AVIStreamHeader, BitmapInfoHeader),
     End(AVIStreamHeader, BitmapInfoHeader),
-    Error,
+}
+
+/// why a chunk was skipped instead of parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// `JUNK`, `JUNQ` or `PAD `, used for alignment or reserved space
+    Padding,
+    /// a known chunk we cannot decode yet
+    Unimplemented,
+    /// a chunk we do not know about
+    Unknown,
+    /// bytes stepped over in a `movi` list to find the next data chunk after an implausible
+    /// chunk header, `tag` being the one of that header and `size` the length of the range,
+    /// or a `LIST INFO` larger than its parent, `size` being the one of its header
+    Corrupted,
+}
+
+/// a chunk the state machine stepped over
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedChunk {
+    /// absolute offset of the chunk header
+    pub offset: usize,
+    pub tag: FourCC,
+    /// size of the chunk content, without the header and padding
+    pub size: u32,
+    pub reason: SkipReason,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,161 +64,597 @@ pub struct List {
     current: parser::List,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Context {
     file_size: usize,
     stream_offset: usize,
+    riff_end: usize,
     level: Vec<List>,
-    video: Option<VideoContext>,
+    main_header: Option<MainAVIHeader>,
+    extended_header: Option<ExtendedAVIHeader>,
+    metadata: Metadata,
+    streams: Vec<StreamContext>,
+    movi_lists: Vec<MoviList>,
+    index: Option<Index>,
+    super_indexes: Vec<SuperIndex>,
+    opendml_index: Index,
+    recover: bool,
+    /// offset and tag of the corrupted chunk we are looking for the next chunk after
+    resync_start: Option<(usize, FourCC)>,
+}
+
+/// position of a `movi` list, there is one in each `RIFF` chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoviList {
+    /// absolute offset of the `movi` fourcc
+    pub offset: usize,
+    /// absolute offset of the end of the list
+    pub end_offset: usize,
+    /// absolute offset of the end of the `RIFF` chunk holding the list
+    pub riff_end: usize,
+}
+
+impl MoviList {
+    /// whether the chunk at `offset` is part of the list
+    pub fn contains(&self, offset: usize) -> bool {
+        self.offset < offset && offset < self.end_offset
+    }
+}
+
+impl State {
+    /// the parsing context, once the file header was read
+    pub fn context_mut(&mut self) -> Option<&mut Context> {
+        match self {
+            State::Initial => None,
+            State::Blocks(ctx)
+            | State::VideoIndexStream(ctx, _)
+            | State::AudioIndexStream(ctx, _)
+            | State::SubtitleIndexStream(ctx, _)
+            | State::Packet(ctx, _)
+            | State::End(ctx) => Some(ctx),
+        }
+    }
+}
+
+impl Context {
+    /// absolute offset of the next chunk to parse
+    pub fn stream_offset(&self) -> usize {
+        self.stream_offset
+    }
+
+    pub fn main_header(&self) -> Option<&MainAVIHeader> {
+        self.main_header.as_ref()
+    }
+
+    /// the OpenDML `dmlh` header, if the file has one
+    pub fn extended_header(&self) -> Option<&ExtendedAVIHeader> {
+        self.extended_header.as_ref()
+    }
+
+    /// the entries of the `LIST INFO` chunks seen so far
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// the stream descriptions, the position in the list being the stream number
+    pub fn streams(&self) -> &[StreamContext] {
+        &self.streams
+    }
+
+    /// whether the next chunk to parse is part of a `movi` list
+    pub fn in_movi(&self) -> bool {
+        self.level
+            .iter()
+            .any(|l| matches!(l.current, parser::List::Movi(_)))
+    }
+
+    /// the `movi` lists seen so far, in file order
+    ///
+    /// OpenDML files have one in the first `RIFF AVI ` chunk, then one in each
+    /// `RIFF AVIX` extension chunk. Together they make the whole stream data.
+    pub fn movi_lists(&self) -> &[MoviList] {
+        &self.movi_lists
+    }
+
+    /// the OpenDML index if the file has one, the legacy `idx1` otherwise
+    pub fn index(&self) -> Option<&Index> {
+        if !self.opendml_index.streams.is_empty() {
+            Some(&self.opendml_index)
+        } else {
+            self.index.as_ref()
+        }
+    }
+
+    /// the OpenDML super indexes found in the stream headers, to load the standard
+    /// indexes the data has not been read for
+    pub fn super_indexes(&self) -> &[SuperIndex] {
+        &self.super_indexes
+    }
+
+    /// in recovery mode, an implausible chunk header in a `movi` list is skipped along with
+    /// the data following it, up to the next data chunk of a known stream. Otherwise it
+    /// fails the parsing.
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+
+    /// context to parse the data chunks of `movi` from `offset`, to resume reading
+    /// after seeking there
+    pub fn seek_movi(&self, movi: &MoviList, offset: usize) -> Context {
+        let mut ctx = self.clone();
+        ctx.stream_offset = offset;
+        ctx.riff_end = movi.riff_end;
+        ctx.level = vec![List {
+            end_offset: movi.end_offset,
+            current: parser::List::Movi(movi.end_offset),
+        }];
+        if !ctx.movi_lists.contains(movi) {
+            ctx.movi_lists.push(movi.clone());
+        }
+
+        ctx
+    }
+
+    /// context to parse the `RIFF AVIX` chunk following the one holding `movi`
+    pub fn seek_next_riff(&self, movi: &MoviList) -> Context {
+        let mut ctx = self.clone();
+        ctx.stream_offset = movi.riff_end;
+        ctx.riff_end = movi.riff_end;
+        ctx.level = Vec::new();
+
+        ctx
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamContext {
+    Video(VideoContext),
+    Audio(AudioContext),
+    Subtitle(SubtitleContext),
+}
+
+impl StreamContext {
+    pub fn header(&self) -> &AVIStreamHeader {
+        match self {
+            StreamContext::Video(v) => &v.stream,
+            StreamContext::Audio(a) => &a.stream,
+            StreamContext::Subtitle(s) => &s.stream,
+        }
+    }
+
+    /// the `strn` title, if the stream has one
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            StreamContext::Video(v) => v.name.as_deref(),
+            StreamContext::Audio(a) => a.name.as_deref(),
+            StreamContext::Subtitle(s) => s.name.as_deref(),
+        }
+    }
+
+    /// the `strd` codec driver data, if the stream has some
+    pub fn codec_data(&self) -> Option<&[u8]> {
+        match self {
+            StreamContext::Video(v) => v.codec_data.as_deref(),
+            StreamContext::Audio(a) => a.codec_data.as_deref(),
+            StreamContext::Subtitle(s) => s.codec_data.as_deref(),
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut Option<String> {
+        match self {
+            StreamContext::Video(v) => &mut v.name,
+            StreamContext::Audio(a) => &mut a.name,
+            StreamContext::Subtitle(s) => &mut s.name,
+        }
+    }
+
+    fn codec_data_mut(&mut self) -> &mut Option<Vec<u8>> {
+        match self {
+            StreamContext::Video(v) => &mut v.codec_data,
+            StreamContext::Audio(a) => &mut a.codec_data,
+            StreamContext::Subtitle(s) => &mut s.codec_data,
+        }
+    }
+
+    pub fn codec(&self) -> CodecId {
+        CodecId::new(self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct VideoContext {
-    stream: AVIStreamHeader,
-    bitmap: BitmapInfoHeader,
+    pub stream: AVIStreamHeader,
+    pub bitmap: BitmapInfoHeader,
+    pub name: Option<String>,
+    pub codec_data: Option<Vec<u8>>,
+    pub properties: Option<VideoPropHeader>,
 }
 
-pub fn advance(state: State, input: &[u8]) -> (usize, State) {
+impl VideoContext {
+    /// the display aspect ratio from the `vprp` chunk, or from the frame dimensions for
+    /// square pixels
+    pub fn display_aspect_ratio(&self) -> Option<(u32, u32)> {
+        if let Some(ratio) = self.properties.as_ref().and_then(|p| p.aspect_ratio()) {
+            return Some(ratio);
+        }
+
+        let (width, height) = (self.bitmap.width(), self.bitmap.height().unsigned_abs());
+        let gcd = gcd(width.unsigned_abs(), height);
+        (gcd != 0).then(|| (width.unsigned_abs() / gcd, height / gcd))
+    }
+
+    /// whether the frames hold two fields, only known from the `vprp` chunk
+    pub fn interlaced(&self) -> bool {
+        self.properties.as_ref().is_some_and(|p| p.interlaced())
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioContext {
+    pub stream: AVIStreamHeader,
+    pub format: WaveFormatEx,
+    pub name: Option<String>,
+    pub codec_data: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleContext {
+    pub stream: AVIStreamHeader,
+    pub name: Option<String>,
+    pub codec_data: Option<Vec<u8>>,
+}
+
+pub fn advance(state: State, input: &[u8]) -> Result<(usize, State), AviError> {
+    advance_with(state, input, &mut |_| {})
+}
+
+/// same as `advance()`, calling `on_skip` for each chunk stepped over
+///
+/// the returned advancing count can be larger than `input` when a chunk is skipped, the
+/// caller then has to seek, or discard data, to the new position
+pub fn advance_with(
+    state: State,
+    input: &[u8],
+    on_skip: &mut dyn FnMut(&SkippedChunk),
+) -> Result<(usize, State), AviError> {
     match state {
         State::Initial => parse_initial(input),
-        State::Blocks(context) => parse_blocks(input, context),
+        State::Blocks(context) | State::Packet(context, _) => parse_blocks(input, context, on_skip),
         State::VideoIndexStream(mut context, index_state) => {
-            match parse_video_index_stream(input, &mut context, index_state) {
-                (_, VideoIndexState::Error) => (0, State::Error),
+            match parse_video_index_stream(input, &mut context, index_state)? {
                 (advancing, VideoIndexState::End(stream, bitmap)) => {
                     context.stream_offset += advancing;
-                    context.video = Some(VideoContext { stream, bitmap });
-                    (advancing, State::Blocks(context))
+                    context.streams.push(StreamContext::Video(VideoContext {
+                        stream,
+                        bitmap,
+                        name: None,
+                        codec_data: None,
+                        properties: None,
+                    }));
+                    Ok((advancing, State::Blocks(context)))
                 }
                 (advancing, video_state) => {
                     context.stream_offset += advancing;
-                    (advancing, State::VideoIndexStream(context, video_state))
+                    Ok((advancing, State::VideoIndexStream(context, video_state)))
                 }
             }
         }
-        State::AudioIndexStream(context) => parse_audio_index_stream(input, context),
-        State::SubtitleIndexStream(context) => parse_subtitle_index_stream(input, context),
-        _ => panic!("unimplemented state"),
+        State::End(context) => Ok((0, State::End(context))),
+        State::AudioIndexStream(context, header) => {
+            parse_audio_index_stream(input, context, header)
+        }
+        State::SubtitleIndexStream(context, header) => {
+            parse_subtitle_index_stream(input, context, header)
+        }
     }
 }
 
-pub fn parse_initial(input: &[u8]) -> (usize, State) {
+pub fn parse_initial(input: &[u8]) -> Result<(usize, State), AviError> {
+    if input.len() < 12 {
+        return Ok((0, State::Initial));
+    }
+
     match header(input) {
-        Err(Err::Error(e)) => {
-            println!("got error: {:?}", e);
-            (0, State::Error)
-        }
-        Err(Err::Failure(f)) => {
-            println!("got failure: {:?}", f);
-            (0, State::Error)
-        }
-        Err(Err::Incomplete(_)) => (0, State::Initial),
-        Ok((i, header)) => (
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Err(AviError::from_nom(input, 0, e)),
+        Err(Err::Incomplete(_)) => Ok((0, State::Initial)),
+        Ok((i, header)) => Ok((
             input.offset(i),
             State::Blocks(Context {
                 file_size: header.file_size as usize,
                 stream_offset: input.offset(i),
+                riff_end: riff_end(0, header.file_size),
                 level: Vec::new(),
-                video: None,
+                main_header: None,
+                extended_header: None,
+                metadata: Metadata::default(),
+                streams: Vec::new(),
+                movi_lists: Vec::new(),
+                index: None,
+                super_indexes: Vec::new(),
+                opendml_index: Index::default(),
+                recover: false,
+                resync_start: None,
             }),
-        ),
+        )),
     }
 }
 
-pub fn unpack_list(input: &[u8], mut ctx: Context) -> (&[u8], Context) {
+/// end of a `RIFF` chunk starting at `offset`, with its padding byte
+fn riff_end(offset: usize, size: u32) -> usize {
+    offset + 8 + size as usize + (size & 1) as usize
+}
+
+/// size of the chunk starting `input`, header and padding included, or `None` if
+/// `input` does not hold it entirely yet
+fn complete_chunk(input: &[u8]) -> Option<usize> {
+    let size = u32::from_le_bytes(input.get(4..8)?.try_into().ok()?);
+    let len = 8 + size as usize + (size & 1) as usize;
+
+    if input.len() >= len {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+pub fn unpack_list(input: &[u8], mut ctx: Context) -> Result<(&[u8], Context), AviError> {
     loop {
         if ctx.level.is_empty() {
-            return (input, ctx);
+            let end_offset = ctx.riff_end.saturating_sub(ctx.stream_offset);
+            return Ok((&input[..min(end_offset, input.len())], ctx));
         } else {
             let end_offset = ctx.level[ctx.level.len() - 1].end_offset;
             match ctx.stream_offset.cmp(&end_offset) {
                 Ordering::Less => {
-                    return (
+                    return Ok((
                         &input[..min(end_offset - ctx.stream_offset, input.len())],
                         ctx,
-                    )
+                    ))
                 }
                 Ordering::Equal => {
                     ctx.level.pop();
                 }
                 Ordering::Greater => {
-                    panic!("the stream offset should never get farther than the list's end");
+                    // the previous chunk went farther than the list's end
+                    return Err(AviError::new(
+                        ctx.stream_offset,
+                        None,
+                        AviErrorKind::ChunkOverflow {
+                            end_offset: ctx.stream_offset,
+                            parent_end: end_offset,
+                        },
+                    ));
                 }
             }
         }
     }
 }
 
-pub fn parse_blocks(input: &[u8], ctx: Context) -> (usize, State) {
-    let (sl, mut ctx) = unpack_list(input, ctx);
+/// parses the `RIFF AVIX` header starting the next part of an OpenDML file
+///
+/// any other data means the file ended with the previous `RIFF`
+pub fn parse_extension(input: &[u8], mut ctx: Context) -> Result<(usize, State), AviError> {
+    match extension_header(input) {
+        Err(Err::Error(_)) | Err(Err::Failure(_)) => Ok((0, State::End(ctx))),
+        Err(Err::Incomplete(_)) => Ok((0, State::Blocks(ctx))),
+        Ok((i, header)) => {
+            ctx.riff_end = riff_end(ctx.stream_offset, header.file_size);
+            let advancing = input.offset(i);
+            ctx.stream_offset += advancing;
+            Ok((advancing, State::Blocks(ctx)))
+        }
+    }
+}
+
+pub fn parse_blocks(
+    input: &[u8],
+    ctx: Context,
+    on_skip: &mut dyn FnMut(&SkippedChunk),
+) -> Result<(usize, State), AviError> {
+    let (sl, mut ctx) = unpack_list(input, ctx)?;
+
+    if ctx.level.is_empty() && ctx.stream_offset >= ctx.riff_end {
+        return parse_extension(input, ctx);
+    }
+
+    if sl.len() < 8 {
+        return Ok((0, State::Blocks(ctx)));
+    }
+
+    let tag = FourCC([sl[0], sl[1], sl[2], sl[3]]);
+    let size = u32::from_le_bytes([sl[4], sl[5], sl[6], sl[7]]);
+    let parent_end = ctx.level.last().map_or(ctx.riff_end, |l| l.end_offset);
+    if ctx.recover && ctx.in_movi() && !plausible_chunk(&ctx, sl, parent_end) {
+        return Ok(resync(sl, ctx, parent_end, on_skip));
+    }
+    if let Some((start, tag)) = ctx.resync_start.take() {
+        // the next chunk was at the end of the data searched by `resync()`
+        on_skip(&SkippedChunk {
+            offset: start,
+            tag,
+            size: (ctx.stream_offset - start) as u32,
+            reason: SkipReason::Corrupted,
+        });
+    }
+
+    let end_offset = ctx.stream_offset + 8 + size as usize;
+    if tag == FourCC::LIST && end_offset > parent_end && list_type(sl) == Some(FourCC::INFO) {
+        // parsed whole, it would wait for data past its parent, the rest of the parent
+        // is skipped instead
+        on_skip(&SkippedChunk {
+            offset: ctx.stream_offset,
+            tag,
+            size,
+            reason: SkipReason::Corrupted,
+        });
+        let advancing = parent_end - ctx.stream_offset;
+        ctx.stream_offset = parent_end;
+        return Ok((advancing, State::Blocks(ctx)));
+    }
+    if tag != FourCC::LIST && end_offset > parent_end {
+        return Err(AviError::chunk(
+            sl,
+            ctx.stream_offset,
+            AviErrorKind::ChunkOverflow {
+                end_offset,
+                parent_end,
+            },
+        ));
+    }
 
-    match block(sl, ctx.stream_offset, ctx.file_size as u32) {
-        Err(Err::Error(e)) => {
-            println!("got error: {:?}", e);
-            (0, State::Error)
+    match block(sl, ctx.stream_offset, ctx.riff_end) {
+        // a stream type we cannot describe, like `mids` or `iavs`
+        Err(Err::Error(_)) | Err(Err::Failure(_))
+            if tag == FourCC::STRH && fcc_type(sl.get(8..).unwrap_or_default()).is_err() =>
+        {
+            Err(AviError::chunk(
+                sl,
+                ctx.stream_offset,
+                AviErrorKind::UnsupportedStream,
+            ))
         }
-        Err(Err::Failure(f)) => {
-            println!("got failure: {:?}", f);
-            (0, State::Error)
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            Err(AviError::from_nom(sl, ctx.stream_offset, e))
         }
-        Err(Err::Incomplete(_)) => (0, State::Blocks(ctx)),
+        Err(Err::Incomplete(_)) => Ok((0, State::Blocks(ctx))),
         Ok((i, blk)) => {
-            let advancing = input.offset(i);
+            let offset = ctx.stream_offset;
+            let mut advancing = input.offset(i);
+
+            let data_chunk = match blk {
+                Block::Default if ctx.in_movi() => data_chunk(sl, offset).ok().map(|(_, c)| c),
+                _ => None,
+            };
+            let skipped = match blk {
+                _ if data_chunk.is_some() => None,
+                Block::Junk => Some(SkipReason::Padding),
+                Block::Unimplemented => Some(SkipReason::Unimplemented),
+                Block::Default => Some(SkipReason::Unknown),
+                _ => None,
+            };
+            if data_chunk.is_some() || skipped.is_some() {
+                // chunks are word aligned, odd sizes are followed by a padding byte
+                advancing = 8 + size as usize + (size & 1) as usize;
+            }
+            if let Some(reason) = skipped {
+                on_skip(&SkippedChunk {
+                    offset,
+                    tag,
+                    size,
+                    reason,
+                });
+            }
+
             ctx.stream_offset += advancing;
+            if let Some(chunk) = data_chunk {
+                return Ok((advancing, State::Packet(ctx, chunk)));
+            }
+
             match blk {
-                Block::Unimplemented => {
-                    panic!("unimplemented block:\n{}", &input[..advancing].to_hex(16))
+                Block::Junk | Block::Unimplemented | Block::Default => {
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                // they follow the `strh` and `strf` of the stream they describe
+                Block::Strn(name) => {
+                    if let Some(stream) = ctx.streams.last_mut() {
+                        *stream.name_mut() = Some(name);
+                    }
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                Block::Strd(data) => {
+                    if let Some(stream) = ctx.streams.last_mut() {
+                        *stream.codec_data_mut() = Some(data);
+                    }
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                Block::Vprp(properties) => {
+                    if let Some(StreamContext::Video(v)) = ctx.streams.last_mut() {
+                        v.properties = Some(properties);
+                    }
+                    Ok((advancing, State::Blocks(ctx)))
                 }
-                Block::Default => panic!("default block:\n{}", &input[..advancing].to_hex(16)),
                 Block::Avih(h) => {
-                    println!("got main AVI header: {:?}\n", h);
-                    (advancing, State::Blocks(ctx))
+                    ctx.main_header = Some(h);
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                Block::Info(metadata) => {
+                    ctx.metadata.extend(metadata);
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                Block::Dmlh(h) => {
+                    ctx.extended_header = Some(h);
+                    Ok((advancing, State::Blocks(ctx)))
                 }
-                Block::Strh(h) => {
-                    println!("got AVI stream header: {:?}\n", h);
-                    match h.fcc_type {
-                        FccType::Video => {
-                            if ctx.video.is_none() {
-                                (
-                                    advancing,
-                                    State::VideoIndexStream(ctx, VideoIndexState::Initial(h)),
-                                )
-                            } else {
-                                (0, State::Error)
-                            }
+                Block::Strh(h) => match h.fcc_type {
+                    FccType::Video => Ok((
+                        advancing,
+                        State::VideoIndexStream(ctx, VideoIndexState::Initial(h)),
+                    )),
+                    FccType::Audio => Ok((advancing, State::AudioIndexStream(ctx, h))),
+                    FccType::Subtitle => Ok((advancing, State::SubtitleIndexStream(ctx, h))),
+                },
+                Block::Idx1(entries) => {
+                    match ctx.movi_lists.first() {
+                        Some(movi) => {
+                            ctx.index = Some(Index::from_idx1(&entries, movi.offset as u64));
+                            Ok((advancing, State::Blocks(ctx)))
                         }
-                        FccType::Audio => (advancing, State::AudioIndexStream(ctx)),
-                        FccType::Subtitle => (advancing, State::SubtitleIndexStream(ctx)),
+                        // the offsets cannot be resolved without the movi list
+                        None => Err(AviError::chunk(
+                            input,
+                            offset,
+                            AviErrorKind::UnexpectedChunk,
+                        )),
                     }
                 }
+                Block::OpenDmlIndex(OpenDmlIndex::Super(index)) => {
+                    ctx.super_indexes.push(index);
+                    Ok((advancing, State::Blocks(ctx)))
+                }
+                Block::OpenDmlIndex(OpenDmlIndex::Standard(index)) => {
+                    ctx.opendml_index.add_standard_index(&index);
+                    Ok((advancing, State::Blocks(ctx)))
+                }
                 Block::List(size, l) => {
-                    if ctx.level.is_empty() {
-                        (
-                            advancing,
-                            State::Blocks(Context {
-                                file_size: ctx.file_size,
-                                stream_offset: ctx.stream_offset,
-                                level: vec![List {
-                                    end_offset: ctx.stream_offset + size,
-                                    current: l,
-                                }],
-                                video: None,
-                            }),
-                        )
-                    } else if ctx.level[ctx.level.len() - 1].end_offset < ctx.stream_offset + size {
+                    // the list size counts the list type we just read
+                    let end_offset = match l {
+                        parser::List::Movi(end_offset) => end_offset,
+                        _ => ctx.stream_offset - 4 + size + (size & 1),
+                    };
+
+                    if let parser::List::Movi(_) = l {
+                        // the list data, and the index offsets, start at the `movi` fourcc
+                        ctx.movi_lists.push(MoviList {
+                            offset: ctx.stream_offset - 4,
+                            end_offset,
+                            riff_end: ctx.riff_end,
+                        });
+                    }
+
+                    let parent_end = ctx.level.last().map_or(ctx.riff_end, |l| l.end_offset);
+                    if parent_end < end_offset {
                         // the new list would be larger than the parent one
-                        println!(
-                            "the new list would be larger ({}) than the parent one ({})",
-                            ctx.stream_offset + size,
-                            ctx.level[ctx.level.len() - 1].end_offset
-                        );
-                        (advancing, State::Error)
+                        Err(AviError::chunk(
+                            input,
+                            offset,
+                            AviErrorKind::ChunkOverflow {
+                                end_offset,
+                                parent_end,
+                            },
+                        ))
                     } else {
                         ctx.level.push(List {
-                            end_offset: ctx.stream_offset + size,
+                            end_offset,
                             current: l,
                         });
-                        (advancing, State::Blocks(ctx))
+                        Ok((advancing, State::Blocks(ctx)))
                     }
                 }
             }
@@ -194,105 +662,481 @@ pub fn parse_blocks(input: &[u8], ctx: Context) -> (usize, State) {
     }
 }
 
+/// the type of the list starting `input`, once it is available
+fn list_type(input: &[u8]) -> Option<FourCC> {
+    input.get(8..).and_then(FourCC::from_slice)
+}
+
+/// whether the chunk starting `input` could be in a `movi` list
+fn plausible_chunk(ctx: &Context, input: &[u8], parent_end: usize) -> bool {
+    let size = u32::from_le_bytes([input[4], input[5], input[6], input[7]]);
+    if ctx.stream_offset + 8 + size as usize > parent_end {
+        return false;
+    }
+
+    match &input[..4] {
+        b"LIST" | b"JUNK" | b"JUNQ" | b"PAD " | b"idx1" => true,
+        [b'i', b'x', ..] => true,
+        tag => stream_chunk(ctx, tag),
+    }
+}
+
+/// whether `tag` is a data chunk id of a known stream
+fn stream_chunk(ctx: &Context, tag: &[u8]) -> bool {
+    stream_number(tag).is_some_and(|n| (n as usize) < ctx.streams.len())
+        && !matches!(chunk_kind(tag), ChunkKind::Unknown(_))
+}
+
+/// steps over the data of `input` up to the next data chunk, or the end of the parent list
+///
+/// the skipped range is reported once the next chunk is found, it can span several calls
+fn resync(
+    input: &[u8],
+    mut ctx: Context,
+    parent_end: usize,
+    on_skip: &mut dyn FnMut(&SkippedChunk),
+) -> (usize, State) {
+    let (start, tag) = *ctx.resync_start.get_or_insert((
+        ctx.stream_offset,
+        FourCC([input[0], input[1], input[2], input[3]]),
+    ));
+
+    let found = (1..input.len().saturating_sub(7)).find(|&p| {
+        let size = u32::from_le_bytes([input[p + 4], input[p + 5], input[p + 6], input[p + 7]]);
+        stream_chunk(&ctx, &input[p..p + 4])
+            && ctx.stream_offset + p + 8 + size as usize <= parent_end
+    });
+    let advancing = match found {
+        Some(p) => p,
+        None if ctx.stream_offset + input.len() >= parent_end => input.len(),
+        // the next chunk header could start in the last 7 bytes
+        None => input.len().saturating_sub(7),
+    };
+
+    ctx.stream_offset += advancing;
+    if found.is_some() || ctx.stream_offset >= parent_end {
+        ctx.resync_start = None;
+        on_skip(&SkippedChunk {
+            offset: start,
+            tag,
+            size: (ctx.stream_offset - start) as u32,
+            reason: SkipReason::Corrupted,
+        });
+    }
+
+    (advancing, State::Blocks(ctx))
+}
+
 pub fn parse_video_index_stream(
     input: &[u8],
     ctx: &mut Context,
     state: VideoIndexState,
-) -> (usize, VideoIndexState) {
+) -> Result<(usize, VideoIndexState), AviError> {
     match state {
-        VideoIndexState::Initial(header) => match strf(input) {
-            Err(Err::Error(e)) => {
-                println!("got error: {:?}", e);
-                (0, VideoIndexState::Error)
-            }
-            Err(Err::Failure(f)) => {
-                println!("got failure: {:?}", f);
-                (0, VideoIndexState::Error)
+        VideoIndexState::Initial(header) => {
+            if complete_chunk(input).is_none() {
+                return Ok((0, VideoIndexState::Initial(header)));
             }
-            Err(Err::Incomplete(_)) => (0, VideoIndexState::Initial(header)),
-            Ok((i, bmp_header)) => {
-                println!("got a bitmap info header: {:?}\n", bmp_header);
-                let advancing = input.offset(i);
-                (advancing, VideoIndexState::BMP(header, bmp_header))
-            }
-        },
-        VideoIndexState::BMP(header, bmp_header) => {
-            match tuple::<_, _, Error<_>, _>((tag(b"JUNK"), le_u32))(input) {
-                Err(Err::Error(e)) => {
-                    println!("got error: {:?}", e);
-                    (0, VideoIndexState::Error)
-                }
-                Err(Err::Failure(f)) => {
-                    println!("got failure: {:?}", f);
-                    (0, VideoIndexState::Error)
-                }
-                Err(Err::Incomplete(_)) => (0, VideoIndexState::BMP(header, bmp_header)),
-                Ok((i, (_, sz))) => {
-                    loop {
-                        if !ctx.level.is_empty() {
-                            let end_offset = ctx.level[ctx.level.len() - 1].end_offset;
-                            println!(
-                                "stream offset == {} end offset == {}",
-                                ctx.stream_offset, end_offset
-                            );
-                            if ctx.stream_offset + 4 == end_offset {
-                                let _ = ctx.level.pop();
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
 
-                    let advancing = input.offset(i) + sz as usize;
-                    (advancing, VideoIndexState::End(header, bmp_header))
+            match strf(input) {
+                Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+                    Err(AviError::from_nom(input, ctx.stream_offset, e))
+                }
+                Err(Err::Incomplete(_)) => Ok((0, VideoIndexState::Initial(header))),
+                Ok((i, bmp_header)) => {
+                    let advancing = input.offset(i);
+                    Ok((advancing, VideoIndexState::End(header, bmp_header)))
                 }
             }
         }
-        _ => unimplemented!(),
+        VideoIndexState::End(..) => Err(AviError::new(
+            ctx.stream_offset,
+            None,
+            AviErrorKind::InvalidState,
+        )),
     }
 }
 
-pub fn parse_audio_index_stream(_input: &[u8], _ctx: Context) -> (usize, State) {
-    unimplemented!()
+pub fn parse_audio_index_stream(
+    input: &[u8],
+    mut ctx: Context,
+    header: AVIStreamHeader,
+) -> Result<(usize, State), AviError> {
+    if complete_chunk(input).is_none() {
+        return Ok((0, State::AudioIndexStream(ctx, header)));
+    }
+
+    match strf_audio(input) {
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            Err(AviError::from_nom(input, ctx.stream_offset, e))
+        }
+        Err(Err::Incomplete(_)) => Ok((0, State::AudioIndexStream(ctx, header))),
+        Ok((i, format)) => {
+            let advancing = input.offset(i);
+            ctx.stream_offset += advancing;
+            ctx.streams.push(StreamContext::Audio(AudioContext {
+                stream: header,
+                format,
+                name: None,
+                codec_data: None,
+            }));
+            Ok((advancing, State::Blocks(ctx)))
+        }
+    }
 }
 
-pub fn parse_subtitle_index_stream(_input: &[u8], _ctx: Context) -> (usize, State) {
-    unimplemented!()
+/// subtitle streams have no standard format header, an eventual `strf` is stepped over
+pub fn parse_subtitle_index_stream(
+    input: &[u8],
+    mut ctx: Context,
+    header: AVIStreamHeader,
+) -> Result<(usize, State), AviError> {
+    if input.len() < 8 {
+        return Ok((0, State::SubtitleIndexStream(ctx, header)));
+    }
+
+    let advancing = if &input[..4] == b"strf" {
+        let size = u32::from_le_bytes([input[4], input[5], input[6], input[7]]);
+        8 + size as usize + (size & 1) as usize
+    } else {
+        0
+    };
+
+    ctx.stream_offset += advancing;
+    ctx.streams.push(StreamContext::Subtitle(SubtitleContext {
+        stream: header,
+        name: None,
+        codec_data: None,
+    }));
+    Ok((advancing, State::Blocks(ctx)))
 }
 
-/*#[cfg(test)]
+#[cfg(test)]
 #[allow(non_upper_case_globals)]
 mod tests {
     use super::*;
+    use crate::info::InfoTag;
+    use crate::parser::ChunkKind;
 
     const drop: &[u8] = include_bytes!("../assets/drop.avi");
-    const _verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
 
-    #[test]
-    fn state_initial() {
-        let mut opt_state = Some(State::Initial);
+    /// feeds the whole `data` to the state machine, returns the last state and the offset
+    /// where it stopped
+    fn run(data: &[u8]) -> Result<(State, usize), AviError> {
+        let mut state = State::Initial;
         let mut offset = 0usize;
-        let data = drop;
 
         loop {
-            if offset > data.len() {
-                println!("file ended");
-                break;
+            let (mv, next) = advance(state.clone(), &data[min(offset, data.len())..])?;
+            if mv == 0 && next == state {
+                return Ok((next, offset));
             }
+            offset += mv;
+            state = next;
+        }
+    }
 
-            println!(
-                "\nwill parse:\n{}\n",
-                &data[offset..offset + 512].to_hex(16)
-            );
-            let state = opt_state.take().expect("should not be none here");
-            let (mv, state) = advance(state, &data[offset..]);
+    fn chunk(tag: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut data = tag.to_vec();
+        data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        data.extend_from_slice(content);
+        data
+    }
+
+    #[test]
+    fn follow_riff_extensions() {
+        let movi = chunk(b"LIST", b"movi");
+        let mut data = chunk(b"RIFF", &[&b"AVI "[..], &movi].concat());
+        data.extend(chunk(b"RIFF", &[&b"AVIX"[..], &movi].concat()));
+        data.extend(chunk(b"RIFF", &[&b"AVIX"[..], &movi].concat()));
+
+        let (state, offset) = run(&data).unwrap();
+        assert_eq!(offset, data.len());
+        let ctx = match state {
+            State::Blocks(ctx) => ctx,
+            s => panic!("unexpected state: {:?}", s),
+        };
+        assert_eq!(
+            ctx.movi_lists(),
+            &[
+                MoviList {
+                    offset: 20,
+                    end_offset: 24,
+                    riff_end: 24,
+                },
+                MoviList {
+                    offset: 44,
+                    end_offset: 48,
+                    riff_end: 48,
+                },
+                MoviList {
+                    offset: 68,
+                    end_offset: 72,
+                    riff_end: 72,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_errors() {
+        let strh = chunk(b"strh", b"vids\0\0\0\0");
+        let hdrl = chunk(b"LIST", &[&b"hdrl"[..], &strh].concat());
+        let data = chunk(b"RIFF", &[&b"AVI "[..], &hdrl].concat());
+        let e = run(&data).unwrap_err();
+        println!("error: {}", e);
+        assert_eq!(e.offset, 40);
+        assert_eq!(e.tag, Some(FourCC::STRH));
+        assert_eq!(e.kind, AviErrorKind::Malformed(nom::error::ErrorKind::Eof));
+
+        let strh = chunk(b"strh", &[&b"mids"[..], &[0; 52]].concat());
+        let hdrl = chunk(b"LIST", &[&b"hdrl"[..], &strh].concat());
+        let data = chunk(b"RIFF", &[&b"AVI "[..], &hdrl].concat());
+        let e = run(&data).unwrap_err();
+        println!("error: {}", e);
+        assert_eq!(e.offset, 24);
+        assert_eq!(e.tag, Some(FourCC::STRH));
+        assert_eq!(e.kind, AviErrorKind::UnsupportedStream);
+
+        let overflow = [&b"JUNK"[..], &100u32.to_le_bytes(), &[0; 4]].concat();
+        let hdrl = chunk(b"LIST", &[&b"hdrl"[..], &overflow].concat());
+        let data = chunk(b"RIFF", &[&b"AVI "[..], &hdrl].concat());
+        let e = run(&data).unwrap_err();
+        assert_eq!(
+            e.kind,
+            AviErrorKind::ChunkOverflow {
+                end_offset: 132,
+                parent_end: 36,
+            }
+        );
+    }
+
+    fn walk(data: &[u8]) -> (Context, Vec<SkippedChunk>, Vec<DataChunk>) {
+        walk_with(data, false, usize::MAX)
+    }
 
-            println!("state is: {:?} (advancing {})", state, mv);
+    /// walks `data` in recovery mode or not, giving the state machine at least `window`
+    /// bytes at once
+    fn walk_with(
+        data: &[u8],
+        recover: bool,
+        window: usize,
+    ) -> (Context, Vec<SkippedChunk>, Vec<DataChunk>) {
+        let mut state = State::Initial;
+        let mut offset = 0usize;
+        let mut len = window;
+        let mut skipped = Vec::new();
+        let mut chunks = Vec::new();
+
+        loop {
+            let start = min(offset, data.len());
+            let input = &data[start..min(start.saturating_add(len), data.len())];
+            let (mv, next) = advance_with(state, input, &mut |c| skipped.push(c.clone())).unwrap();
             offset += mv;
+            len = if mv == 0 {
+                len.saturating_add(window)
+            } else {
+                window
+            };
+
+            match next {
+                State::End(ctx) => return (ctx, skipped, chunks),
+                State::Blocks(ctx) if mv == 0 && offset >= data.len() => {
+                    return (ctx, skipped, chunks)
+                }
+                State::Packet(ctx, chunk) => {
+                    chunks.push(chunk);
+                    state = State::Blocks(ctx);
+                }
+                State::Blocks(mut ctx) => {
+                    ctx.set_recover(recover);
+                    state = State::Blocks(ctx);
+                }
+                next => state = next,
+            }
+        }
+    }
+
+    #[test]
+    fn walk_drop() {
+        let (ctx, skipped, chunks) = walk(drop);
+        assert!(matches!(ctx.streams(), [StreamContext::Video(_)]));
+        assert_eq!(ctx.stream_offset, 675636);
+        assert_eq!(ctx.index().unwrap().stream(0).unwrap().len(), 182);
+
+        assert_eq!(
+            skipped[0],
+            SkippedChunk {
+                offset: 212,
+                tag: FourCC::JUNK,
+                size: 1816,
+                reason: SkipReason::Padding,
+            }
+        );
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            chunks[0],
+            DataChunk {
+                offset: 2048,
+                tag: FourCC::new(b"00db"),
+                stream: 0,
+                kind: ChunkKind::UncompressedVideo,
+                size: 2686,
+            }
+        );
+        assert_eq!(chunks.len(), 182);
+    }
+
+    #[test]
+    fn walk_verona() {
+        let (ctx, skipped, chunks) = walk(verona);
+        assert!(matches!(
+            ctx.streams(),
+            [StreamContext::Video(_), StreamContext::Audio(_)]
+        ));
+        assert_eq!(chunks.iter().filter(|c| c.stream == 1).count(), 62);
+        assert_eq!(ctx.movi_lists().len(), 1);
+        assert_eq!(ctx.index().unwrap().stream(1).unwrap().len(), 62);
 
-            opt_state = Some(state);
+        assert!(skipped
+            .iter()
+            .all(|c| c.reason != SkipReason::Unimplemented));
+        assert_eq!(ctx.streams()[0].name(), Some("Video stream"));
+        assert_eq!(ctx.streams()[1].name(), Some("Audio stream"));
+        assert_eq!(ctx.streams()[0].codec_data(), None);
+        match &ctx.streams()[0] {
+            StreamContext::Video(v) => {
+                assert_eq!(v.display_aspect_ratio(), Some((4, 3)));
+                assert!(!v.interlaced());
+            }
+            s => panic!("unexpected stream {:?}", s),
         }
     }
-}*/
+
+    #[test]
+    fn skip_odd_sized_chunks() {
+        let hdrl = [
+            &b"hdrl"[..],
+            &chunk(b"ISFT", b"odd"),
+            &[0],
+            &chunk(b"JUNQ", &[0; 4]),
+        ]
+        .concat();
+        let data = chunk(b"RIFF", &[&b"AVI "[..], &chunk(b"LIST", &hdrl)].concat());
+        let (ctx, skipped, _) = walk(&data);
+        assert_eq!(ctx.stream_offset, data.len());
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[1].offset, 36);
+    }
+
+    #[test]
+    fn walk_info_lists() {
+        let info = [
+            &b"INFO"[..],
+            &chunk(b"INAM", b"title\0"),
+            &chunk(b"ISFT", b"avi\0"),
+        ]
+        .concat();
+        let hdrl = [&b"hdrl"[..], &chunk(b"LIST", &info)].concat();
+        let info = [&b"INFO"[..], &chunk(b"ISFT", b"remux\0")].concat();
+        let riff = [&b"AVI "[..], &chunk(b"LIST", &hdrl), &chunk(b"LIST", &info)].concat();
+        let data = chunk(b"RIFF", &riff);
+
+        let (ctx, skipped, _) = walk(&data);
+        assert!(skipped.is_empty());
+        assert_eq!(ctx.metadata().get(InfoTag::Title), Some("title"));
+        assert_eq!(ctx.metadata().get(InfoTag::Software), Some("remux"));
+    }
+
+    #[test]
+    fn skip_oversized_info_list() {
+        let info = [&b"INFO"[..], &chunk(b"INAM", b"title\0")].concat();
+        let oversized = [&b"LIST"[..], &1000u32.to_le_bytes(), &info].concat();
+        let hdrl = [&b"hdrl"[..], &oversized].concat();
+        let info = [&b"INFO"[..], &chunk(b"ISFT", b"remux\0")].concat();
+        let movi = [&b"movi"[..], &chunk(b"00dc", b"ab")].concat();
+        let riff = [
+            &b"AVI "[..],
+            &chunk(b"LIST", &hdrl),
+            &chunk(b"LIST", &info),
+            &chunk(b"LIST", &movi),
+        ]
+        .concat();
+        let data = chunk(b"RIFF", &riff);
+
+        let (ctx, skipped, chunks) = walk(&data);
+        assert_eq!(
+            skipped,
+            vec![SkippedChunk {
+                offset: 24,
+                tag: FourCC::LIST,
+                size: 1000,
+                reason: SkipReason::Corrupted,
+            }]
+        );
+        assert_eq!(ctx.metadata().get(InfoTag::Title), None);
+        assert_eq!(ctx.metadata().get(InfoTag::Software), Some("remux"));
+        assert_eq!(chunks.len(), 1);
+    }
+
+    #[test]
+    fn walk_rec_lists() {
+        let rec = [
+            &b"rec "[..],
+            &chunk(b"00dc", b"abcd"),
+            &chunk(b"01wb", b"ef"),
+        ]
+        .concat();
+        let movi = [&b"movi"[..], &chunk(b"LIST", &rec), &chunk(b"00dc", b"gh")].concat();
+        let data = chunk(b"RIFF", &[&b"AVI "[..], &chunk(b"LIST", &movi)].concat());
+
+        let (_, _, chunks) = walk(&data);
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|c| (c.offset, c.stream, c.kind))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (36, 0, ChunkKind::CompressedVideo),
+                (48, 1, ChunkKind::Audio),
+                (58, 0, ChunkKind::CompressedVideo),
+            ]
+        );
+    }
+
+    #[test]
+    fn resync_after_corrupted_chunks() {
+        let mut data = verona.to_vec();
+        // the first chunk size overflows the movi list
+        data[2052..2056].copy_from_slice(&0xfffff0u32.to_le_bytes());
+        // the next video chunk has a stream number that doesn't exist
+        data[12430..12432].copy_from_slice(b"07");
+
+        let e = run(&data).unwrap_err();
+        assert_eq!(e.offset, 2048);
+        assert!(matches!(e.kind, AviErrorKind::ChunkOverflow { .. }));
+
+        for window in [usize::MAX, 1000, 7] {
+            let (ctx, skipped, chunks) = walk_with(&data, true, window);
+            assert_eq!(ctx.stream_offset, 1926668);
+            assert_eq!(chunks.len(), 832);
+            assert_eq!(chunks[0].offset, 6790);
+            assert_eq!(chunks[1].offset, 13244);
+
+            let corrupted: Vec<_> = skipped
+                .iter()
+                .filter(|c| c.reason == SkipReason::Corrupted)
+                .map(|c| (c.offset, c.tag, c.size))
+                .collect();
+            assert_eq!(
+                corrupted,
+                vec![
+                    (2048, FourCC::new(b"00db"), 4742),
+                    (12430, FourCC::new(b"07dc"), 814)
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use crate::parser::ChunkKind;
+use crate::state::StreamContext;
+
+/// a position on a stream timeline, lasting `value * scale / rate` seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub value: u64,
+    pub scale: u32,
+    pub rate: u32,
+}
+
+impl Timestamp {
+    pub fn new(value: u64, scale: u32, rate: u32) -> Timestamp {
+        Timestamp { value, scale, rate }
+    }
+
+    /// the last timestamp at or before `duration`, in `scale / rate` seconds units
+    pub fn from_duration(duration: Duration, scale: u32, rate: u32) -> Timestamp {
+        let den = scale as u128 * 1_000_000_000;
+        let value = match den {
+            0 => 0,
+            _ => duration.as_nanos() * rate as u128 / den,
+        };
+
+        Timestamp::new(value as u64, scale, rate)
+    }
+
+    /// the timestamp as a fraction of seconds, `(numerator, denominator)`
+    pub fn rational(&self) -> (u128, u128) {
+        (self.value as u128 * self.scale as u128, self.rate as u128)
+    }
+
+    /// zero when the stream has no valid rate
+    pub fn duration(&self) -> Duration {
+        let (num, den) = self.rational();
+        if den == 0 {
+            return Duration::ZERO;
+        }
+
+        let nanos = num * 1_000_000_000 / den;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        if self.rate == 0 {
+            return 0.0;
+        }
+        self.value as f64 * self.scale as f64 / self.rate as f64
+    }
+}
+
+/// counts the time units elapsed in a stream as its chunks are read
+///
+/// video and subtitle chunks hold one frame each. Audio streams with a `sample_size`
+/// are constant bitrate and counted in samples, variable bitrate ones in `block_align`
+/// sized blocks, as seen on
+/// <https://learn.microsoft.com/en-us/windows/win32/directshow/avi-riff-file-reference>
+///
+/// the default clock counts chunks without a valid rate, for undeclared streams
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamClock {
+    scale: u32,
+    rate: u32,
+    start: u64,
+    sample_size: u32,
+    block_align: u32,
+    audio: bool,
+    /// time units since the start of the stream
+    ticks: u64,
+}
+
+impl StreamClock {
+    pub fn new(stream: &StreamContext) -> StreamClock {
+        let header = stream.header();
+        let (audio, block_align) = match stream {
+            StreamContext::Audio(a) => (true, a.format.block_align as u32),
+            _ => (false, 0),
+        };
+
+        StreamClock {
+            scale: header.scale(),
+            rate: header.rate(),
+            start: header.start() as u64,
+            sample_size: header.sample_size(),
+            block_align,
+            audio,
+            ticks: 0,
+        }
+    }
+
+    /// how many time units a chunk of `size` bytes lasts
+    pub fn ticks(&self, size: u32) -> u64 {
+        if !self.audio {
+            return 1;
+        }
+
+        if self.sample_size != 0 {
+            size as u64 / self.sample_size as u64
+        } else if self.block_align > 1 {
+            (size as u64).div_ceil(self.block_align as u64)
+        } else {
+            1
+        }
+    }
+
+    /// timestamp of the next chunk
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::new(self.start + self.ticks, self.scale, self.rate)
+    }
+
+    /// returns the timestamp of a chunk of `size` bytes and moves past it
+    pub fn advance(&mut self, size: u32) -> Timestamp {
+        let timestamp = self.timestamp();
+        self.ticks += self.ticks(size);
+        timestamp
+    }
+
+    /// moves to `ticks` time units after the start of the stream
+    pub fn set_ticks(&mut self, ticks: u64) {
+        self.ticks = ticks;
+    }
+
+    /// time units between the start of the stream and the start of each chunk, given by
+    /// kind and size, followed by the end of the last one
+    ///
+    /// palette changes take no time
+    pub fn chunk_ticks(&self, chunks: impl Iterator<Item = (ChunkKind, u32)>) -> Vec<u64> {
+        let mut ticks = vec![0];
+        for (kind, size) in chunks {
+            let last = ticks[ticks.len() - 1];
+            ticks.push(match kind {
+                ChunkKind::PaletteChange => last,
+                _ => last + self.ticks(size),
+            });
+        }
+
+        ticks
+    }
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use super::*;
+    use crate::state::{advance, State};
+
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    fn verona_streams() -> Vec<StreamContext> {
+        let mut state = State::Initial;
+        let mut offset = 0;
+        loop {
+            let (advancing, next) = advance(state, &verona[offset..]).unwrap();
+            offset += advancing;
+            match next {
+                State::Blocks(ctx) if ctx.in_movi() => return ctx.streams().to_vec(),
+                next => state = next,
+            }
+        }
+    }
+
+    #[test]
+    fn timestamp_duration() {
+        let t = Timestamp::new(3, 80000, 1000000);
+        assert_eq!(t.rational(), (240000, 1000000));
+        assert_eq!(t.duration(), Duration::from_millis(240));
+        assert_eq!(Timestamp::new(3, 1, 0).duration(), Duration::ZERO);
+
+        let t = Timestamp::from_duration(Duration::from_millis(250), 80000, 1000000);
+        assert_eq!(t, Timestamp::new(3, 80000, 1000000));
+    }
+
+    #[test]
+    fn clocks() {
+        let streams = verona_streams();
+
+        let mut video = StreamClock::new(&streams[0]);
+        video.advance(2686);
+        video.advance(10);
+        assert_eq!(video.advance(0).duration(), Duration::from_millis(160));
+
+        // 256 bytes samples at 5644/256 per second
+        let mut audio = StreamClock::new(&streams[1]);
+        assert_eq!(audio.advance(5632).value, 0);
+        let t = audio.advance(5644);
+        assert_eq!(t.value, 22);
+        assert_eq!(t.duration(), Duration::from_nanos(997_873_848));
+        assert_eq!(
+            audio.chunk_ticks([(ChunkKind::Audio, 5632), (ChunkKind::Audio, 5644)].into_iter()),
+            vec![0, 22, 44]
+        );
+    }
+
+    #[test]
+    fn vbr_audio_clock() {
+        let mut clock = StreamClock::new(&verona_streams()[1]);
+        clock.sample_size = 0;
+        assert_eq!(clock.ticks(256), 1);
+        assert_eq!(clock.ticks(257), 2);
+        clock.block_align = 0;
+        assert_eq!(clock.ticks(5632), 1);
+    }
+}
@@ -0,0 +1,727 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::error::{AviError, AviErrorKind};
+use crate::fourcc::FourCC;
+use crate::index::{
+    Idx1Entry, IndexEntry, SuperIndexEntry, AVIIF_KEYFRAME, AVISTDINDEX_DELTAFRAME,
+    AVI_INDEX_OF_CHUNKS, AVI_INDEX_OF_INDEXES,
+};
+use crate::parser::{
+    chunk_kind, AVIStreamHeader, BlockHeader, ExtendedAVIHeader, Header, MainAVIHeader,
+    AVIF_HASINDEX, AVIF_ISINTERLEAVED, BI_RGB,
+};
+use crate::state::{StreamContext, VideoContext};
+use crate::time::StreamClock;
+
+/// default size limit of the `RIFF` chunks of OpenDML files
+const SEGMENT_SIZE: u64 = 1 << 30;
+/// largest `RIFF` chunk, so its size and the offsets relative to it fit in 32 bits
+const MAX_SEGMENT_SIZE: u64 = u32::MAX as u64;
+/// the stream number is written in two decimal digits in the chunk ids
+const MAX_STREAMS: usize = 100;
+/// number of standard indexes, so of `RIFF` chunks, the super indexes have room for
+const SUPER_INDEX_ENTRIES: usize = 256;
+
+/// muxes packets into an AVI file, with a legacy `idx1` index
+///
+/// the headers are written on creation from the stream descriptions, then the packets in
+/// the order they are interleaved in. `finish()` writes the index and fixes the sizes and
+/// counts in the headers, the file is not valid before.
+pub struct AviWriter<W> {
+    writer: W,
+    /// absolute position of the next chunk
+    position: u64,
+    main_header: MainAVIHeader,
+    streams: Vec<StreamContext>,
+    clocks: Vec<StreamClock>,
+    /// number of chunks written in each stream
+    chunk_counts: Vec<u32>,
+    hdrl_offset: u64,
+    /// absolute offset of the current `RIFF` chunk
+    riff_offset: u64,
+    /// absolute offset of the current `movi` fourcc, the base of the `idx1` offsets
+    movi_offset: u64,
+    /// entries of the `idx1` index, which only covers the first `RIFF` chunk
+    index: Vec<Idx1Entry>,
+    opendml: Option<OpenDml>,
+}
+
+/// state of the OpenDML indexes
+struct OpenDml {
+    segment_size: u64,
+    /// chunks of the current `RIFF` chunk, by stream
+    chunks: Vec<Vec<IndexEntry>>,
+    /// standard indexes written so far, by stream
+    super_indexes: Vec<Vec<SuperIndexEntry>>,
+    /// duration of each stream at the start of the current `RIFF` chunk
+    segment_start: Vec<u64>,
+    /// frames in the first `RIFF` chunk, once it is complete
+    first_riff_frames: Option<u32>,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    /// writes the file headers for `streams`, the stream numbers being their positions, so
+    /// there can be at most 100 of them
+    ///
+    /// the file is limited to 4 GiB, in a single `RIFF` chunk. The stream lengths and
+    /// buffer sizes are filled in by `finish()`.
+    pub fn new(writer: W, streams: &[StreamContext]) -> Result<AviWriter<W>, AviError> {
+        AviWriter::with_opendml(writer, streams, false)
+    }
+
+    /// same as `new()`, writing an OpenDML file without size limit
+    ///
+    /// the data is split in 1 GiB `RIFF` chunks, each one with standard indexes listed in
+    /// the super index of their stream. The first `RIFF` chunk also has an `idx1` index,
+    /// for the readers not supporting OpenDML.
+    pub fn new_opendml(writer: W, streams: &[StreamContext]) -> Result<AviWriter<W>, AviError> {
+        AviWriter::with_opendml(writer, streams, true)
+    }
+
+    fn with_opendml(
+        mut writer: W,
+        streams: &[StreamContext],
+        opendml: bool,
+    ) -> Result<AviWriter<W>, AviError> {
+        if streams.len() > MAX_STREAMS {
+            return Err(AviError::new(
+                0,
+                None,
+                AviErrorKind::TooManyStreams(streams.len()),
+            ));
+        }
+        let position = writer.stream_position().map_err(|e| AviError::io(0, e))?;
+
+        let mut streams = streams.to_vec();
+        for stream in &mut streams {
+            let header = stream_header_mut(stream);
+            header.length = 0;
+            header.suggested_buffer_size = 0;
+        }
+
+        let mut avi = AviWriter {
+            writer,
+            position,
+            main_header: main_header(&streams),
+            clocks: streams.iter().map(StreamClock::new).collect(),
+            chunk_counts: vec![0; streams.len()],
+            opendml: opendml.then(|| OpenDml {
+                segment_size: SEGMENT_SIZE,
+                chunks: vec![Vec::new(); streams.len()],
+                super_indexes: vec![Vec::new(); streams.len()],
+                segment_start: vec![0; streams.len()],
+                first_riff_frames: None,
+            }),
+            streams,
+            hdrl_offset: position + 12,
+            riff_offset: position,
+            movi_offset: 0,
+            index: Vec::new(),
+        };
+
+        let hdrl = avi.hdrl();
+        let mut header = Header::new(0, FourCC::AVI).to_bytes();
+        header.extend_from_slice(&hdrl);
+        header.extend_from_slice(&movi_header());
+        avi.write(&header)?;
+        avi.movi_offset = avi.position - 4;
+
+        Ok(avi)
+    }
+
+    /// maximum size of the `RIFF` chunks of OpenDML files, 1 GiB by default and at most
+    /// 4 GiB
+    pub fn set_segment_size(&mut self, size: u64) {
+        if let Some(opendml) = &mut self.opendml {
+            opendml.segment_size = size.min(MAX_SEGMENT_SIZE);
+        }
+    }
+
+    /// appends a chunk of `stream` data
+    pub fn write_packet(
+        &mut self,
+        stream: u16,
+        data: &[u8],
+        keyframe: bool,
+    ) -> Result<(), AviError> {
+        let chunk_id = match self.streams.get(stream as usize) {
+            Some(s) => chunk_id(stream, s),
+            None => {
+                return Err(AviError::new(
+                    self.position as usize,
+                    None,
+                    AviErrorKind::UnknownStream(stream),
+                ))
+            }
+        };
+
+        let too_large = |position: u64| {
+            AviError::new(
+                position as usize,
+                Some(chunk_id),
+                AviErrorKind::FileTooLarge,
+            )
+        };
+        let size = u32::try_from(data.len()).map_err(|_| too_large(self.position))?;
+
+        let len = 8 + data.len() as u64 + (data.len() & 1) as u64;
+        if self.segment_end(len) - self.riff_offset > self.segment_size() {
+            let started = match &self.opendml {
+                Some(o) => o.chunks.iter().any(|c| !c.is_empty()),
+                None => false,
+            };
+            if started {
+                self.end_segment()?;
+                self.start_segment()?;
+            }
+        }
+        // the chunk does not fit even in an empty `RIFF` chunk
+        if self.segment_end(len) - self.riff_offset > self.segment_size() {
+            return Err(too_large(self.position));
+        }
+
+        if self.riff_offset == self.hdrl_offset - 12 {
+            self.index.push(Idx1Entry {
+                chunk_id,
+                flags: if keyframe { AVIIF_KEYFRAME } else { 0 },
+                offset: (self.position - self.movi_offset) as u32,
+                size,
+            });
+        }
+        if let Some(opendml) = &mut self.opendml {
+            opendml.chunks[stream as usize].push(IndexEntry {
+                offset: self.position,
+                size,
+                keyframe,
+                kind: chunk_kind(chunk_id.as_bytes()),
+            });
+        }
+        self.write(&chunk(chunk_id, data))?;
+
+        let stream = stream as usize;
+        self.clocks[stream].advance(size);
+        self.chunk_counts[stream] += 1;
+        let header = stream_header_mut(&mut self.streams[stream]);
+        header.suggested_buffer_size = header.suggested_buffer_size.max(size);
+
+        Ok(())
+    }
+
+    /// writes the indexes and updates the headers, returns the underlying writer
+    pub fn finish(mut self) -> Result<W, AviError> {
+        self.end_segment()?;
+        let end = self.position;
+
+        for (stream, clock) in self.streams.iter_mut().zip(&self.clocks) {
+            let start = stream.header().start as u64;
+            stream_header_mut(stream).length = (clock.timestamp().value - start) as u32;
+        }
+        self.main_header.total_frames = match &self.opendml {
+            // the main header only counts the frames readers without OpenDML support see
+            Some(o) => o.first_riff_frames.unwrap_or(0),
+            None => self.video_frames(),
+        };
+        self.main_header.suggested_buffer_size = self
+            .streams
+            .iter()
+            .map(|s| s.header().suggested_buffer_size)
+            .max()
+            .unwrap_or(0);
+
+        let hdrl = self.hdrl();
+        self.write_at(self.hdrl_offset, &hdrl)?;
+        self.seek_to(end)?;
+
+        Ok(self.writer)
+    }
+
+    fn segment_size(&self) -> u64 {
+        match &self.opendml {
+            Some(o) => o.segment_size,
+            None => MAX_SEGMENT_SIZE,
+        }
+    }
+
+    /// end of the current `RIFF` chunk, with its indexes, if a chunk of `len` bytes was
+    /// added to it
+    fn segment_end(&self, len: u64) -> u64 {
+        let mut end = self.position + len;
+        if self.riff_offset == self.hdrl_offset - 12 {
+            end += 8 + 16 * (self.index.len() as u64 + 1);
+        }
+        if let Some(opendml) = &self.opendml {
+            // the standard indexes, the new chunk adding an entry to one of them
+            end += 8;
+            for chunks in &opendml.chunks {
+                end += 32 + 8 * chunks.len() as u64;
+            }
+        }
+
+        end
+    }
+
+    fn video_frames(&self) -> u32 {
+        self.streams
+            .iter()
+            .position(|s| matches!(s, StreamContext::Video(_)))
+            .map_or(0, |v| self.chunk_counts[v])
+    }
+
+    /// writes the indexes ending the current `RIFF` chunk, and fixes the chunk sizes
+    fn end_segment(&mut self) -> Result<(), AviError> {
+        let first = self.riff_offset == self.hdrl_offset - 12;
+
+        let mut indexes = Vec::new();
+        if let Some(opendml) = &self.opendml {
+            for (stream, chunks) in opendml.chunks.iter().enumerate() {
+                if chunks.is_empty() {
+                    continue;
+                }
+                if opendml.super_indexes[stream].len() == SUPER_INDEX_ENTRIES {
+                    return Err(self.too_large());
+                }
+
+                let chunk_id = chunk_id(stream as u16, &self.streams[stream]);
+                let ix = standard_index_bytes(chunk_id, self.riff_offset, chunks)
+                    .ok_or_else(|| self.too_large())?;
+                indexes.push((stream, ix));
+            }
+        }
+
+        for (stream, ix) in indexes {
+            let offset = self.position;
+            self.write(&ix)?;
+
+            let duration =
+                self.clocks[stream].timestamp().value - self.streams[stream].header().start as u64;
+            let too_large = self.too_large();
+            if let Some(opendml) = &mut self.opendml {
+                opendml.super_indexes[stream].push(SuperIndexEntry {
+                    offset,
+                    size: ix.len() as u32,
+                    duration: u32::try_from(duration - opendml.segment_start[stream])
+                        .map_err(|_| too_large)?,
+                });
+                opendml.segment_start[stream] = duration;
+            }
+        }
+
+        let frames = self.video_frames();
+        if let Some(opendml) = &mut self.opendml {
+            opendml.chunks.iter_mut().for_each(Vec::clear);
+            if first {
+                opendml.first_riff_frames = Some(frames);
+            }
+        }
+
+        let movi_end = self.position;
+        let movi_size = u32::try_from(movi_end - self.movi_offset).map_err(|_| self.too_large())?;
+        self.write_at(self.movi_offset - 4, &movi_size.to_le_bytes())?;
+
+        if first {
+            let mut idx1 = Vec::with_capacity(self.index.len() * 16);
+            for entry in &self.index {
+                idx1.extend_from_slice(entry.chunk_id.as_bytes());
+                idx1.extend_from_slice(&entry.flags.to_le_bytes());
+                idx1.extend_from_slice(&entry.offset.to_le_bytes());
+                idx1.extend_from_slice(&entry.size.to_le_bytes());
+            }
+            self.seek_to(movi_end)?;
+            self.write(&chunk(FourCC::IDX1, &idx1))?;
+        }
+
+        let end = self.position;
+        let riff_size = u32::try_from(end - self.riff_offset - 8).map_err(|_| self.too_large())?;
+        self.write_at(self.riff_offset + 4, &riff_size.to_le_bytes())?;
+        self.seek_to(end)
+    }
+
+    /// starts a `RIFF AVIX` chunk and its `movi` list
+    fn start_segment(&mut self) -> Result<(), AviError> {
+        self.riff_offset = self.position;
+        let mut header = Header::new(0, FourCC::AVIX).to_bytes();
+        header.extend_from_slice(&movi_header());
+        self.write(&header)?;
+        self.movi_offset = self.position - 4;
+
+        Ok(())
+    }
+
+    /// the `hdrl` list, its size does not change once the streams are known
+    fn hdrl(&self) -> Vec<u8> {
+        let mut hdrl = FourCC::HDRL.as_bytes().to_vec();
+        hdrl.extend(chunk(FourCC::AVIH, &self.main_header.to_bytes()));
+
+        for (n, stream) in self.streams.iter().enumerate() {
+            let mut strl = FourCC::STRL.as_bytes().to_vec();
+            strl.extend(chunk(FourCC::STRH, &stream.header().to_bytes()));
+            match stream {
+                StreamContext::Video(v) => strl.extend(chunk(FourCC::STRF, &v.bitmap.to_bytes())),
+                StreamContext::Audio(a) => strl.extend(chunk(FourCC::STRF, &a.format.to_bytes())),
+                StreamContext::Subtitle(_) => {}
+            }
+            if let Some(data) = stream.codec_data() {
+                strl.extend(chunk(FourCC::STRD, data));
+            }
+            if let StreamContext::Video(VideoContext {
+                properties: Some(p),
+                ..
+            }) = stream
+            {
+                strl.extend(chunk(FourCC::VPRP, &p.to_bytes()));
+            }
+            if let Some(name) = stream.name() {
+                strl.extend(chunk(FourCC::STRN, &[name.as_bytes(), &[0]].concat()));
+            }
+            if let Some(opendml) = &self.opendml {
+                let chunk_id = chunk_id(n as u16, stream);
+                let indx = super_index_bytes(chunk_id, &opendml.super_indexes[n]);
+                strl.extend(chunk(FourCC::INDX, &indx));
+            }
+            hdrl.extend(chunk(FourCC::LIST, &strl));
+        }
+
+        if self.opendml.is_some() {
+            let dmlh = ExtendedAVIHeader {
+                grand_frames: self.video_frames(),
+            }
+            .to_bytes();
+            let odml = [&FourCC::ODML.0[..], &chunk(FourCC::DMLH, &dmlh)].concat();
+            hdrl.extend(chunk(FourCC::LIST, &odml));
+        }
+
+        chunk(FourCC::LIST, &hdrl)
+    }
+
+    fn too_large(&self) -> AviError {
+        AviError::new(self.position as usize, None, AviErrorKind::FileTooLarge)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), AviError> {
+        self.writer
+            .write_all(data)
+            .map_err(|e| AviError::io(self.position as usize, e))?;
+        self.position += data.len() as u64;
+        Ok(())
+    }
+
+    fn seek_to(&mut self, offset: u64) -> Result<(), AviError> {
+        self.writer
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| AviError::io(offset as usize, e))?;
+        Ok(())
+    }
+
+    /// writes `data` at `offset`, the position being left after it
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), AviError> {
+        self.writer
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.writer.write_all(data))
+            .map_err(|e| AviError::io(offset as usize, e))
+    }
+}
+
+/// the main header for `streams`, the frame rate and dimensions being the first video
+/// stream ones
+fn main_header(streams: &[StreamContext]) -> MainAVIHeader {
+    let video = streams.iter().find_map(|s| match s {
+        StreamContext::Video(v) => Some(v),
+        _ => None,
+    });
+    let (microsec_per_frame, width, height) = match video {
+        Some(v) => (
+            match v.stream.rate {
+                0 => 0,
+                rate => (v.stream.scale as u64 * 1_000_000 / rate as u64) as u32,
+            },
+            v.bitmap.width.unsigned_abs(),
+            v.bitmap.height.unsigned_abs(),
+        ),
+        None => (0, 0, 0),
+    };
+
+    MainAVIHeader::new(microsec_per_frame, streams.len() as u32, width, height)
+        .with_flags(AVIF_HASINDEX | AVIF_ISINTERLEAVED)
+}
+
+fn stream_header_mut(stream: &mut StreamContext) -> &mut AVIStreamHeader {
+    match stream {
+        StreamContext::Video(v) => &mut v.stream,
+        StreamContext::Audio(a) => &mut a.stream,
+        StreamContext::Subtitle(s) => &mut s.stream,
+    }
+}
+
+/// `##db` for uncompressed frames, `##dc` for compressed ones, `##wb` for audio and
+/// `##tx` for subtitles
+fn chunk_id(stream: u16, context: &StreamContext) -> FourCC {
+    let kind = match context {
+        StreamContext::Video(v) if v.bitmap.compression == BI_RGB => b"db",
+        StreamContext::Video(_) => b"dc",
+        StreamContext::Audio(_) => b"wb",
+        StreamContext::Subtitle(_) => b"tx",
+    };
+
+    FourCC([
+        b'0' + (stream / 10 % 10) as u8,
+        b'0' + (stream % 10) as u8,
+        kind[0],
+        kind[1],
+    ])
+}
+
+/// a `movi` list header, its size being set once the list is complete
+fn movi_header() -> Vec<u8> {
+    let mut data = BlockHeader::new(FourCC::LIST, 0).to_bytes();
+    data.extend_from_slice(FourCC::MOVI.as_bytes());
+    data
+}
+
+/// a chunk with its header, and a padding byte if the size is odd
+fn chunk(tag: FourCC, content: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(content.len() + 9);
+    data.extend_from_slice(tag.as_bytes());
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(content);
+    if content.len() % 2 == 1 {
+        data.push(0);
+    }
+    data
+}
+
+/// AVISUPERINDEX content, with room for `SUPER_INDEX_ENTRIES` entries
+fn super_index_bytes(chunk_id: FourCC, entries: &[SuperIndexEntry]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(24 + 16 * SUPER_INDEX_ENTRIES);
+    data.extend_from_slice(&4u16.to_le_bytes());
+    data.push(0);
+    data.push(AVI_INDEX_OF_INDEXES);
+    data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    data.extend_from_slice(chunk_id.as_bytes());
+    data.extend_from_slice(&[0; 12]);
+    for entry in entries {
+        data.extend_from_slice(&entry.offset.to_le_bytes());
+        data.extend_from_slice(&entry.size.to_le_bytes());
+        data.extend_from_slice(&entry.duration.to_le_bytes());
+    }
+    data.resize(24 + 16 * SUPER_INDEX_ENTRIES, 0);
+    data
+}
+
+/// `ix##` chunk of a stream, the offsets being relative to the `RIFF` chunk
+///
+/// returns `None` if a chunk is too far from the start of the `RIFF` chunk
+fn standard_index_bytes(
+    chunk_id: FourCC,
+    base_offset: u64,
+    entries: &[IndexEntry],
+) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(24 + 8 * entries.len());
+    data.extend_from_slice(&2u16.to_le_bytes());
+    data.push(0);
+    data.push(AVI_INDEX_OF_CHUNKS);
+    data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    data.extend_from_slice(chunk_id.as_bytes());
+    data.extend_from_slice(&base_offset.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    for entry in entries {
+        // the entries point to the payload, after the chunk header
+        let offset = u32::try_from(entry.offset + 8 - base_offset).ok()?;
+        let delta = if entry.keyframe {
+            0
+        } else {
+            AVISTDINDEX_DELTAFRAME
+        };
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&(entry.size | delta).to_le_bytes());
+    }
+
+    let tag = FourCC([b'i', b'x', chunk_id.0[0], chunk_id.0[1]]);
+    Some(chunk(tag, &data))
+}
+
+#[cfg(test)]
+#[allow(non_upper_case_globals)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::index::idx1;
+    use crate::parser::ChunkKind;
+    use crate::reader::{AviReader, SeekMode};
+
+    const verona: &[u8] = include_bytes!("../assets/verona60avi56k.avi");
+
+    #[test]
+    fn remux_verona() {
+        let mut reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let streams = reader.streams().to_vec();
+        let mut writer = AviWriter::new(Cursor::new(Vec::new()), &streams).unwrap();
+
+        let mut packets = Vec::new();
+        while let Some(p) = reader.next_packet().unwrap() {
+            writer.write_packet(p.stream, &p.data, p.keyframe).unwrap();
+            packets.push(p);
+        }
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut remuxed = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert_eq!(remuxed.main_header().total_frames, 772);
+        assert_eq!(remuxed.main_header().microsec_per_frame, 80000);
+        assert!(remuxed.extended_header().is_none());
+        assert_eq!(remuxed.duration(), Duration::from_millis(772 * 80));
+        assert_eq!(remuxed.index().unwrap().stream(1).unwrap().len(), 62);
+        assert_eq!(remuxed.streams()[1].name(), Some("Audio stream"));
+        match (&remuxed.streams()[1], &streams[1]) {
+            (StreamContext::Audio(a), StreamContext::Audio(b)) => {
+                assert_eq!(a.format, b.format);
+                let bytes: usize = packets
+                    .iter()
+                    .filter(|p| p.stream == 1)
+                    .map(|p| p.data.len())
+                    .sum();
+                assert_eq!(a.stream.length as usize, bytes / 256);
+            }
+            s => panic!("unexpected streams: {:?}", s),
+        }
+
+        let summary = |p: &crate::reader::Packet| (p.stream, p.data.clone(), p.keyframe, p.pts);
+        let mut count = 0;
+        while let Some(p) = remuxed.next_packet().unwrap() {
+            assert_eq!(summary(&p), summary(&packets[count]));
+            count += 1;
+        }
+        assert_eq!(count, 834);
+    }
+
+    #[test]
+    fn remux_verona_opendml() {
+        let mut reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let mut writer = AviWriter::new_opendml(Cursor::new(Vec::new()), reader.streams()).unwrap();
+        writer.set_segment_size(200_000);
+
+        let mut packets = Vec::new();
+        while let Some(p) = reader.next_packet().unwrap() {
+            writer.write_packet(p.stream, &p.data, p.keyframe).unwrap();
+            packets.push(p);
+        }
+        let data = writer.finish().unwrap().into_inner();
+
+        // RIFF chunks of at most 200000 bytes
+        let mut riffs = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            assert!(size <= 200_000 - 8);
+            riffs.push(offset);
+            offset += 8 + size as usize;
+        }
+        assert_eq!(offset, data.len());
+        assert_eq!(riffs.len(), 10);
+        assert!(riffs[1..].iter().all(|&r| &data[r + 8..r + 12] == b"AVIX"));
+
+        let mut remuxed = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert_eq!(remuxed.index().unwrap().stream(0).unwrap().len(), 772);
+        assert_eq!(remuxed.index().unwrap().stream(1).unwrap().len(), 62);
+        assert!(remuxed.main_header().total_frames < 772);
+        assert_eq!(remuxed.extended_header().unwrap().grand_frames, 772);
+        assert_eq!(remuxed.total_frames(), 772);
+        assert_eq!(remuxed.duration(), Duration::from_millis(772 * 80));
+        assert_eq!(remuxed.streams()[0].header().length, 772);
+
+        // the first RIFF chunk ends with a legacy index of its chunks
+        let first_riff_end = 8 + u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let first_riff = &data[..first_riff_end];
+        let idx1_offset = first_riff.windows(4).rposition(|w| w == b"idx1").unwrap();
+        let (rest, entries) = idx1(&first_riff[idx1_offset..]).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            entries.len(),
+            remuxed
+                .index()
+                .unwrap()
+                .streams
+                .iter()
+                .flat_map(|s| &s.entries)
+                .filter(|e| (e.offset as usize) < first_riff_end)
+                .count()
+        );
+
+        let summary = |p: &crate::reader::Packet| (p.stream, p.data.clone(), p.keyframe, p.pts);
+        let mut count = 0;
+        while let Some(p) = remuxed.next_packet().unwrap() {
+            assert_eq!(summary(&p), summary(&packets[count]));
+            count += 1;
+        }
+        assert_eq!(count, 834);
+
+        // seeking in the last RIFF chunk
+        let time = Duration::from_secs(60);
+        remuxed.seek(0, time, SeekMode::PreviousKeyframe).unwrap();
+        let p = remuxed.next_packet().unwrap().unwrap();
+        let keyframe = packets
+            .iter()
+            .rfind(|p| p.stream == 0 && p.keyframe && p.pts.duration() <= time)
+            .unwrap();
+        assert_eq!(summary(&p), summary(keyframe));
+        assert!(p.offset as usize > riffs[9]);
+    }
+
+    #[test]
+    fn unknown_stream() {
+        let mut writer = AviWriter::new(Cursor::new(Vec::new()), &[]).unwrap();
+        let e = writer.write_packet(0, b"data", true).unwrap_err();
+        assert_eq!(e.kind, AviErrorKind::UnknownStream(0));
+    }
+
+    #[test]
+    fn packet_larger_than_segment() {
+        let reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let mut writer = AviWriter::new_opendml(Cursor::new(Vec::new()), reader.streams()).unwrap();
+        writer.set_segment_size(200_000);
+        writer.write_packet(0, &[0; 1000], true).unwrap();
+
+        // rejected in the current RIFF chunk, then in a new one
+        let e = writer.write_packet(0, &[0; 300_000], true).unwrap_err();
+        assert_eq!(e.kind, AviErrorKind::FileTooLarge);
+        writer.write_packet(0, &[0; 1000], false).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let remuxed = AviReader::new(Cursor::new(&data[..])).unwrap();
+        assert_eq!(remuxed.index().unwrap().stream(0).unwrap().len(), 2);
+        // the second packet went in a new RIFF chunk
+        let first_riff_end = 8 + u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert_eq!(&data[first_riff_end + 8..first_riff_end + 12], b"AVIX");
+        assert!(data.len() - first_riff_end < 200_000);
+    }
+
+    #[test]
+    fn segment_size_limit() {
+        let reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let mut writer = AviWriter::new_opendml(Cursor::new(Vec::new()), reader.streams()).unwrap();
+        writer.set_segment_size(u64::MAX);
+        assert_eq!(writer.segment_size(), u32::MAX as u64);
+
+        // the offsets of the standard indexes are relative to the RIFF chunk
+        let entry = |offset| IndexEntry {
+            offset,
+            size: 10,
+            keyframe: true,
+            kind: ChunkKind::CompressedVideo,
+        };
+        let chunk_id = FourCC::new(b"00dc");
+        assert!(standard_index_bytes(chunk_id, 1 << 32, &[entry((1 << 33) - 9)]).is_some());
+        assert!(standard_index_bytes(chunk_id, 1 << 32, &[entry(1 << 33)]).is_none());
+    }
+
+    #[test]
+    fn too_many_streams() {
+        let reader = AviReader::new(Cursor::new(verona)).unwrap();
+        let streams = vec![reader.streams()[1].clone(); 101];
+        let e = AviWriter::new(Cursor::new(Vec::new()), &streams)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind, AviErrorKind::TooManyStreams(101));
+        assert!(AviWriter::new(Cursor::new(Vec::new()), &streams[..100]).is_ok());
+    }
+}